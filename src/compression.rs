@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use errors::Word2VecError;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// Open a file for reading, decompressing it on the fly if it starts with the magic bytes of
+/// gzip, bzip2 or xz.
+///
+/// Each compression format needs its cargo feature (`gzip`, `bzip2`, `xz`) to be enabled;
+/// otherwise `Word2VecError::UnsupportedCompression` is returned for such files.
+pub fn open_file(file_name: &str) -> Result<Box<dyn BufRead>, Word2VecError> {
+    let file = File::open(file_name)?;
+    let mut reader = BufReader::new(file);
+    let magic = reader.fill_buf()?;
+
+    if magic.starts_with(GZIP_MAGIC) {
+        gzip(reader)
+    } else if is_bzip2(magic) {
+        bzip2(reader)
+    } else if magic.starts_with(XZ_MAGIC) {
+        xz(reader)
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Whether `magic` starts with the bzip2 signature followed by the block size digit, which tells
+/// bzip2 streams apart from text files whose first word starts with "BZh".
+fn is_bzip2(magic: &[u8]) -> bool {
+    magic.len() > BZIP2_MAGIC.len()
+        && magic.starts_with(BZIP2_MAGIC)
+        && (b'1'..=b'9').contains(&magic[BZIP2_MAGIC.len()])
+}
+
+#[cfg(feature = "gzip")]
+fn gzip(reader: BufReader<File>) -> Result<Box<dyn BufRead>, Word2VecError> {
+    use flate2::bufread::MultiGzDecoder;
+    Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gzip(_: BufReader<File>) -> Result<Box<dyn BufRead>, Word2VecError> {
+    Err(Word2VecError::UnsupportedCompression("gzip"))
+}
+
+#[cfg(feature = "bzip2")]
+fn bzip2(reader: BufReader<File>) -> Result<Box<dyn BufRead>, Word2VecError> {
+    use bzip2::bufread::MultiBzDecoder;
+    Ok(Box::new(BufReader::new(MultiBzDecoder::new(reader))))
+}
+
+#[cfg(not(feature = "bzip2"))]
+fn bzip2(_: BufReader<File>) -> Result<Box<dyn BufRead>, Word2VecError> {
+    Err(Word2VecError::UnsupportedCompression("bzip2"))
+}
+
+#[cfg(feature = "xz")]
+fn xz(reader: BufReader<File>) -> Result<Box<dyn BufRead>, Word2VecError> {
+    use xz2::bufread::XzDecoder;
+    Ok(Box::new(BufReader::new(XzDecoder::new_multi_decoder(
+        reader,
+    ))))
+}
+
+#[cfg(not(feature = "xz"))]
+fn xz(_: BufReader<File>) -> Result<Box<dyn BufRead>, Word2VecError> {
+    Err(Word2VecError::UnsupportedCompression("xz"))
+}
@@ -12,6 +12,52 @@ pub enum Word2VecError {
     Io(io::Error),
     Decode(FromUtf8Error),
     WrongHeader,
+    InvalidWord(String),
+    WrongVectorSize {
+        expected: usize,
+        found: usize,
+    },
+    UnknownFormat(String),
+    UnsupportedCompression(&'static str),
+    InvalidMappedFile(String),
+    TruncatedVector {
+        record: usize,
+        offset: u64,
+    },
+    InvalidUtf8Word {
+        record: usize,
+        offset: u64,
+    },
+    InvalidComponent {
+        record: usize,
+        offset: u64,
+        value: String,
+    },
+    VocabularySizeMismatch {
+        expected: usize,
+        found: usize,
+    },
+    TrailingData {
+        offset: u64,
+    },
+    NonFiniteComponent {
+        record: usize,
+        offset: u64,
+    },
+    DimensionMismatch {
+        record: usize,
+        offset: u64,
+        expected: usize,
+        found: usize,
+    },
+    InvalidParameter(String),
+    InvalidQuantizedFile(String),
+    InvalidIndexFile(String),
+    UnknownWords(Vec<String>),
+    InvalidEvaluationFile {
+        line: usize,
+        reason: String,
+    },
 }
 
 impl error::Error for Word2VecError {
@@ -34,6 +80,84 @@ impl fmt::Display for Word2VecError {
             Word2VecError::Io(ref err) => write!(f, "IO error: {}", err),
             Word2VecError::Decode(ref err) => write!(f, "Decode error: {}", err),
             Word2VecError::WrongHeader => write!(f, "Wrong header length."),
+            Word2VecError::InvalidWord(ref word) => {
+                write!(f, "Word {:?} can't be stored in a word2vec file.", word)
+            }
+            Word2VecError::WrongVectorSize { expected, found } => write!(
+                f,
+                "Wrong vector size: expected {} components, found {}.",
+                expected, found
+            ),
+            Word2VecError::UnknownFormat(ref reason) => {
+                write!(f, "Unable to detect the vector file format: {}.", reason)
+            }
+            Word2VecError::UnsupportedCompression(format) => write!(
+                f,
+                "File is compressed with {0}, enable the `{0}` feature to read it.",
+                format
+            ),
+            Word2VecError::InvalidMappedFile(ref reason) => {
+                write!(f, "Invalid memory-mapped vector file: {}.", reason)
+            }
+            Word2VecError::TruncatedVector { record, offset } => {
+                write!(f, "Record {} at byte {} is truncated.", record, offset)
+            }
+            Word2VecError::InvalidUtf8Word { record, offset } => write!(
+                f,
+                "Word of record {} at byte {} is not valid UTF-8.",
+                record, offset
+            ),
+            Word2VecError::InvalidComponent {
+                record,
+                offset,
+                ref value,
+            } => write!(
+                f,
+                "Record {} at byte {} contains the invalid component {:?}.",
+                record, offset, value
+            ),
+            Word2VecError::VocabularySizeMismatch { expected, found } => write!(
+                f,
+                "Header declares {} words, but the file ends after {}.",
+                expected, found
+            ),
+            Word2VecError::TrailingData { offset } => {
+                write!(
+                    f,
+                    "Unexpected data after the last record at byte {}.",
+                    offset
+                )
+            }
+            Word2VecError::NonFiniteComponent { record, offset } => write!(
+                f,
+                "Record {} at byte {} contains NaN or infinite components.",
+                record, offset
+            ),
+            Word2VecError::DimensionMismatch {
+                record,
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Record {} at byte {} has {} components, but the header declares {}.",
+                record, offset, found, expected
+            ),
+            Word2VecError::InvalidParameter(ref reason) => {
+                write!(f, "Invalid parameter: {}.", reason)
+            }
+            Word2VecError::InvalidQuantizedFile(ref reason) => {
+                write!(f, "Invalid product-quantized vector file: {}.", reason)
+            }
+            Word2VecError::InvalidIndexFile(ref reason) => {
+                write!(f, "Invalid nearest neighbor index file: {}.", reason)
+            }
+            Word2VecError::UnknownWords(ref words) => {
+                write!(f, "Words {:?} are not in the vocabulary.", words)
+            }
+            Word2VecError::InvalidEvaluationFile { line, ref reason } => {
+                write!(f, "Invalid evaluation file at line {}: {}.", line, reason)
+            }
         }
     }
 }
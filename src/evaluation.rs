@@ -0,0 +1,474 @@
+//! Evaluation of word vector spaces on standard benchmarks
+//!
+//! [`AnalogyEvaluation`](struct.AnalogyEvaluation.html) scores a model on word analogy questions
+//! in the format of the `questions-words.txt` file distributed with word2vec: sections started by
+//! a line `: name`, each followed by questions of four words `a b c d`, asking "a is to b as c is
+//! to ?" with the expected answer d. The evaluation follows gensim's `evaluate_word_analogies`.
+//!
+//! [`SimilarityEvaluation`](struct.SimilarityEvaluation.html) correlates the cosine similarities
+//! of word pairs with human judgements, as in the WordSim-353, SimLex-999 and MEN data sets. The
+//! evaluation follows gensim's `evaluate_word_pairs`.
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+
+use crate::compression;
+use crate::errors::Word2VecError;
+use crate::wordvectors::{AnalogyMethod, WordVector};
+
+/// Number of answers of each question which are searched for one that isn't part of the question
+const ANSWERS: usize = 5;
+
+/// Maps the words considered by an evaluation to the words of the model
+///
+/// Only the `restrict_vocab` most frequent words are considered. When folding case, the keys are
+/// upper-cased and the most frequent of the words that differ only by case is kept. Words which
+/// can't be looked up by their name, like the lossy names of words kept as raw bytes, are left
+/// out.
+fn vocabulary(
+    model: &WordVector,
+    restrict_vocab: usize,
+    case_insensitive: bool,
+) -> HashMap<String, &str> {
+    let mut vocabulary = HashMap::new();
+    for word in model.get_words().take(restrict_vocab) {
+        if model.index_of(word).is_none() {
+            continue;
+        }
+        let key = if case_insensitive {
+            word.to_uppercase()
+        } else {
+            word.clone()
+        };
+        vocabulary.entry(key).or_insert(word.as_str());
+    }
+    vocabulary
+}
+
+/// Counts of the analogy questions of one section, or of all of them
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SectionScore {
+    /// Name of the section, from its `: name` header
+    pub name: String,
+    /// Questions answered with the expected word
+    pub correct: usize,
+    /// Questions answered with another word
+    pub incorrect: usize,
+    /// Questions skipped because one of their words is not among the considered words
+    pub oov: usize,
+}
+
+impl SectionScore {
+    fn new(name: &str) -> SectionScore {
+        SectionScore {
+            name: name.to_string(),
+            ..SectionScore::default()
+        }
+    }
+
+    /// The share of the answered questions which were answered correctly, 0 if none were.
+    pub fn accuracy(&self) -> f32 {
+        let answered = self.correct + self.incorrect;
+        if answered == 0 {
+            0.0
+        } else {
+            self.correct as f32 / answered as f32
+        }
+    }
+}
+
+/// Result of an [`AnalogyEvaluation`](struct.AnalogyEvaluation.html)
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalogyScores {
+    /// The scores of each section, in the order of the file
+    pub sections: Vec<SectionScore>,
+    /// The scores of all questions, named `Total accuracy` like in gensim
+    pub total: SectionScore,
+}
+
+impl AnalogyScores {
+    /// The share of all questions which were skipped as out of vocabulary.
+    pub fn oov_ratio(&self) -> f32 {
+        let questions = self.total.correct + self.total.incorrect + self.total.oov;
+        if questions == 0 {
+            0.0
+        } else {
+            self.total.oov as f32 / questions as f32
+        }
+    }
+}
+
+/// Evaluation of a model on word analogy questions
+///
+/// Each question `a b c d` is answered with
+/// [`WordVector::analogy_with_restricted`](../wordvectors/struct.WordVector.html#method.analogy_with_restricted)
+/// as `pos = [b, c]` and `neg = [a]`; the best answer which is none of `a`, `b` and `c` is
+/// compared with `d`. Questions which contain a word outside of the `restrict_vocab` most
+/// frequent ones are skipped and counted as out of vocabulary.
+///
+/// ```no_run
+/// use word2vec::evaluation::AnalogyEvaluation;
+/// use word2vec::wordvectors::WordVector;
+///
+/// let model = WordVector::load_from_binary("vectors.bin").unwrap();
+/// let scores = AnalogyEvaluation::new()
+///     .restrict_vocab(30_000)
+///     .evaluate_file(&model, "questions-words.txt")
+///     .unwrap();
+/// for section in &scores.sections {
+///     println!("{}: {:.3}", section.name, section.accuracy());
+/// }
+/// println!("total: {:.3}, OOV: {:.3}", scores.total.accuracy(), scores.oov_ratio());
+/// ```
+#[derive(Clone, Debug)]
+pub struct AnalogyEvaluation {
+    restrict_vocab: usize,
+    case_insensitive: bool,
+    method: AnalogyMethod,
+}
+
+impl Default for AnalogyEvaluation {
+    fn default() -> AnalogyEvaluation {
+        AnalogyEvaluation {
+            restrict_vocab: 300_000,
+            case_insensitive: true,
+            method: AnalogyMethod::CosAdd,
+        }
+    }
+}
+
+impl AnalogyEvaluation {
+    pub fn new() -> AnalogyEvaluation {
+        AnalogyEvaluation::default()
+    }
+
+    /// Only consider the `restrict_vocab` most frequent words, 300000 by default.
+    pub fn restrict_vocab(mut self, restrict_vocab: usize) -> AnalogyEvaluation {
+        self.restrict_vocab = restrict_vocab;
+        self
+    }
+
+    /// Set whether words are compared regardless of their case, which is the default.
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> AnalogyEvaluation {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Set how the candidate answers are scored, `AnalogyMethod::CosAdd` by default.
+    pub fn method(mut self, method: AnalogyMethod) -> AnalogyEvaluation {
+        self.method = method;
+        self
+    }
+
+    /// Evaluate `model` on the questions in a file, which may be compressed
+    pub fn evaluate_file(
+        &self,
+        model: &WordVector,
+        file_name: &str,
+    ) -> Result<AnalogyScores, Word2VecError> {
+        let reader = compression::open_file(file_name)?;
+        self.evaluate(model, reader)
+    }
+
+    /// Evaluate `model` on the questions read from `reader`
+    ///
+    /// Fails with `Word2VecError::InvalidEvaluationFile` if a question comes before the first
+    /// section header or doesn't consist of four words.
+    pub fn evaluate<R: BufRead>(
+        &self,
+        model: &WordVector,
+        reader: R,
+    ) -> Result<AnalogyScores, Word2VecError> {
+        let vocabulary = vocabulary(model, self.restrict_vocab, self.case_insensitive);
+        let fold = |word: &str| {
+            if self.case_insensitive {
+                word.to_uppercase()
+            } else {
+                word.to_string()
+            }
+        };
+
+        let mut sections: Vec<SectionScore> = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let invalid = |reason: &str| Word2VecError::InvalidEvaluationFile {
+                line: number + 1,
+                reason: reason.to_string(),
+            };
+            if let Some(name) = line.strip_prefix(": ") {
+                sections.push(SectionScore::new(name.trim()));
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let section = match sections.last_mut() {
+                Some(section) => section,
+                None => return Err(invalid("question before the first section header")),
+            };
+            let question: Vec<String> = line.split_whitespace().map(fold).collect();
+            if question.len() != 4 {
+                return Err(invalid("question doesn't consist of four words"));
+            }
+            let words: Vec<&str> = match question
+                .iter()
+                .map(|word| vocabulary.get(word).cloned())
+                .collect()
+            {
+                Some(words) => words,
+                None => {
+                    section.oov += 1;
+                    continue;
+                }
+            };
+
+            let result = model.analogy_with_restricted(
+                self.method,
+                vec![words[1], words[2]],
+                vec![words[0]],
+                ANSWERS,
+                self.restrict_vocab,
+            )?;
+            let ignore: HashSet<&String> = question[..3].iter().collect();
+            let answer = result
+                .words
+                .into_iter()
+                .map(|(word, _)| fold(&word))
+                .find(|word| !ignore.contains(word));
+            if answer.as_ref() == Some(&question[3]) {
+                section.correct += 1;
+            } else {
+                section.incorrect += 1;
+            }
+        }
+
+        let mut total = SectionScore::new("Total accuracy");
+        for section in &sections {
+            total.correct += section.correct;
+            total.incorrect += section.incorrect;
+            total.oov += section.oov;
+        }
+        Ok(AnalogyScores { sections, total })
+    }
+}
+
+/// Result of a [`SimilarityEvaluation`](struct.SimilarityEvaluation.html)
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimilarityScores {
+    /// Pearson correlation of the cosine similarities with the human scores
+    pub pearson: f32,
+    /// Spearman rank correlation of the cosine similarities with the human scores
+    pub spearman: f32,
+    /// Pairs of which both words are among the considered words
+    pub pairs: usize,
+    /// Pairs skipped because one of their words is not among the considered words
+    pub oov: usize,
+}
+
+impl SimilarityScores {
+    /// The share of all pairs which were skipped as out of vocabulary.
+    pub fn oov_ratio(&self) -> f32 {
+        let pairs = self.pairs + self.oov;
+        if pairs == 0 {
+            0.0
+        } else {
+            self.oov as f32 / pairs as f32
+        }
+    }
+}
+
+/// Evaluation of a model on the similarity of word pairs
+///
+/// Every line of the file contains two words and a human similarity score, separated by
+/// whitespace or by the configured delimiter; further fields are ignored. Empty lines and lines
+/// starting with `#` are skipped, and so is a first line whose score isn't a number, which is
+/// taken to be a header. Pairs with a word outside of the `restrict_vocab` most frequent ones are
+/// counted as out of vocabulary and left out of the correlations.
+///
+/// The correlations are NaN if fewer than two pairs are known or all scores of one side are equal.
+///
+/// ```no_run
+/// use word2vec::evaluation::SimilarityEvaluation;
+/// use word2vec::wordvectors::WordVector;
+///
+/// let model = WordVector::load_from_binary("vectors.bin").unwrap();
+/// let scores = SimilarityEvaluation::new()
+///     .delimiter(',')
+///     .evaluate_file(&model, "wordsim353.csv")
+///     .unwrap();
+/// println!("Spearman: {:.3}, Pearson: {:.3}", scores.spearman, scores.pearson);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SimilarityEvaluation {
+    restrict_vocab: usize,
+    case_insensitive: bool,
+    delimiter: Option<char>,
+}
+
+impl Default for SimilarityEvaluation {
+    fn default() -> SimilarityEvaluation {
+        SimilarityEvaluation {
+            restrict_vocab: 300_000,
+            case_insensitive: true,
+            delimiter: None,
+        }
+    }
+}
+
+impl SimilarityEvaluation {
+    pub fn new() -> SimilarityEvaluation {
+        SimilarityEvaluation::default()
+    }
+
+    /// Only consider the `restrict_vocab` most frequent words, 300000 by default.
+    pub fn restrict_vocab(mut self, restrict_vocab: usize) -> SimilarityEvaluation {
+        self.restrict_vocab = restrict_vocab;
+        self
+    }
+
+    /// Set whether words are compared regardless of their case, which is the default.
+    pub fn case_insensitive(mut self, case_insensitive: bool) -> SimilarityEvaluation {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Separate the fields of a line by `delimiter` instead of whitespace.
+    pub fn delimiter(mut self, delimiter: char) -> SimilarityEvaluation {
+        self.delimiter = Some(delimiter);
+        self
+    }
+
+    /// Evaluate `model` on the word pairs in a file, which may be compressed
+    pub fn evaluate_file(
+        &self,
+        model: &WordVector,
+        file_name: &str,
+    ) -> Result<SimilarityScores, Word2VecError> {
+        let reader = compression::open_file(file_name)?;
+        self.evaluate(model, reader)
+    }
+
+    /// Evaluate `model` on the word pairs read from `reader`
+    ///
+    /// Fails with `Word2VecError::InvalidEvaluationFile` if a line has fewer than three fields or
+    /// a score which isn't a number.
+    pub fn evaluate<R: BufRead>(
+        &self,
+        model: &WordVector,
+        reader: R,
+    ) -> Result<SimilarityScores, Word2VecError> {
+        let vocabulary = vocabulary(model, self.restrict_vocab, self.case_insensitive);
+        let mut human = Vec::new();
+        let mut cosine = Vec::new();
+        let mut oov = 0;
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let invalid = |reason: &str| Word2VecError::InvalidEvaluationFile {
+                line: number + 1,
+                reason: reason.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = match self.delimiter {
+                Some(delimiter) => line.split(delimiter).map(str::trim).collect(),
+                None => line.split_whitespace().collect(),
+            };
+            if fields.len() < 3 {
+                return Err(invalid("line doesn't consist of two words and a score"));
+            }
+            let score = match fields[2].parse::<f32>() {
+                Ok(score) => score,
+                Err(_) if number == 0 => continue,
+                Err(_) => return Err(invalid("score is not a number")),
+            };
+            let known = |word: &str| {
+                if self.case_insensitive {
+                    vocabulary.get(&word.to_uppercase()).cloned()
+                } else {
+                    vocabulary.get(word).cloned()
+                }
+            };
+            let similarity = match (known(fields[0]), known(fields[1])) {
+                (Some(word1), Some(word2)) => model.similarity(word1, word2),
+                _ => None,
+            };
+            match similarity {
+                Some(similarity) => {
+                    human.push(score);
+                    cosine.push(similarity);
+                }
+                None => oov += 1,
+            }
+        }
+
+        Ok(SimilarityScores {
+            pearson: pearson(&human, &cosine),
+            spearman: pearson(&ranks(&human), &ranks(&cosine)),
+            pairs: human.len(),
+            oov,
+        })
+    }
+}
+
+/// Pearson correlation coefficient of two samples of equal length
+fn pearson(x: &[f32], y: &[f32]) -> f32 {
+    let n = x.len() as f64;
+    let mean_x = x.iter().map(|&v| f64::from(v)).sum::<f64>() / n;
+    let mean_y = y.iter().map(|&v| f64::from(v)).sum::<f64>() / n;
+    let (mut covariance, mut variance_x, mut variance_y) = (0.0, 0.0, 0.0);
+    for (&a, &b) in x.iter().zip(y) {
+        let dx = f64::from(a) - mean_x;
+        let dy = f64::from(b) - mean_y;
+        covariance += dx * dy;
+        variance_x += dx * dx;
+        variance_y += dy * dy;
+    }
+    if x.len() < 2 || variance_x == 0.0 || variance_y == 0.0 {
+        return f32::NAN;
+    }
+    (covariance / (variance_x * variance_y).sqrt()) as f32
+}
+
+/// The ranks of the values, starting at 1, with tied values sharing their average rank
+fn ranks(values: &[f32]) -> Vec<f32> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        // positions start..end hold ranks start + 1 ..= end
+        let rank = (start + end + 1) as f32 / 2.0;
+        for &index in &order[start..end] {
+            ranks[index] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranks() {
+        assert_eq!(
+            ranks(&[0.5, 0.1, 0.5, 0.9, 0.5]),
+            vec![3.0, 1.0, 3.0, 5.0, 3.0]
+        );
+        assert_eq!(ranks(&[2.0, 1.0, 1.0]), vec![3.0, 1.5, 1.5]);
+    }
+
+    #[test]
+    fn test_pearson() {
+        assert!((pearson(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]) - 1.0).abs() < 1e-6);
+        assert!((pearson(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]) + 1.0).abs() < 1e-6);
+        assert!(pearson(&[1.0, 2.0], &[1.0, 1.0]).is_nan());
+        assert!(pearson(&[1.0], &[1.0]).is_nan());
+    }
+}
@@ -0,0 +1,436 @@
+//! Approximate nearest neighbor search with hierarchical navigable small world graphs
+//!
+//! [`WordVector::cosine`](../wordvectors/struct.WordVector.html#method.cosine) compares the query
+//! with every word. An [`HnswIndex`](struct.HnswIndex.html) links each word to some of its
+//! nearest neighbors in a hierarchy of graphs (Malkov and Yashunin, 2016) and answers a query by
+//! a greedy walk through these graphs, which visits only a small part of the vocabulary. The
+//! results are approximate: raising `ef_search` trades speed for recall.
+//!
+//! The index only stores the graph and borrows the vectors from the
+//! [`WordVector`](../wordvectors/struct.WordVector.html) it was built from. The file layout
+//! written by [`save`](struct.HnswIndex.html#method.save) is (all integers little-endian):
+//!
+//! | content |
+//! |---|
+//! | magic `W2VHNSW\0` |
+//! | format version (`u32`), reserved (`u32`) |
+//! | vocabulary size `V`, vector size `D`, `M`, `ef_construction`, `ef_search` (`u64` each) |
+//! | entry point (`u64`, `u64::MAX` if the index is empty) |
+//! | the links of each of the `V` words |
+//!
+//! The links of a word are its number of layers (`u32`) followed, for each layer from the bottom
+//! up, by the number of neighbors (`u32`) and their rows (`u32` each).
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::errors::Word2VecError;
+use crate::search::{Candidate, VectorSpace};
+use crate::utils::{self, Rng};
+use crate::wordvectors::WordVector;
+
+const MAGIC: &[u8; 8] = b"W2VHNSW\0";
+const VERSION: u32 = 1;
+const MAX_LEVEL: usize = 16;
+
+fn invalid(reason: &str) -> Word2VecError {
+    Word2VecError::InvalidIndexFile(reason.to_string())
+}
+
+/// Builder for an [`HnswIndex`](struct.HnswIndex.html)
+///
+/// ```no_run
+/// use word2vec::hnsw::HnswBuilder;
+/// use word2vec::wordvectors::WordVector;
+///
+/// let model = WordVector::load_from_binary("vectors.bin").unwrap();
+/// let index = HnswBuilder::new().m(32).build(&model).unwrap();
+/// println!("{:?}", index.most_similar("snow", 10));
+/// index.save("vectors.hnsw").unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct HnswBuilder {
+    m: usize,
+    ef_construction: usize,
+    seed: u64,
+}
+
+impl Default for HnswBuilder {
+    fn default() -> HnswBuilder {
+        HnswBuilder {
+            m: 16,
+            ef_construction: 200,
+            seed: 0,
+        }
+    }
+}
+
+impl HnswBuilder {
+    pub fn new() -> HnswBuilder {
+        HnswBuilder::default()
+    }
+
+    /// Set the number of neighbors each word is linked to, 16 by default
+    ///
+    /// Words are linked to up to `2 * m` neighbors in the bottom layer. Larger values improve
+    /// recall, especially for high-dimensional vectors, at the cost of memory and build time.
+    pub fn m(mut self, m: usize) -> HnswBuilder {
+        self.m = m;
+        self
+    }
+
+    /// Set the number of candidates considered when linking a word, 200 by default.
+    pub fn ef_construction(mut self, ef_construction: usize) -> HnswBuilder {
+        self.ef_construction = ef_construction;
+        self
+    }
+
+    /// Set the seed of the random layer assignment, so that building is reproducible.
+    pub fn seed(mut self, seed: u64) -> HnswBuilder {
+        self.seed = seed;
+        self
+    }
+
+    /// Build an index over all words of `model`
+    ///
+    /// The links store rows as `u32`, so models of more than `u32::MAX` words are rejected with
+    /// `Word2VecError::InvalidParameter`.
+    pub fn build<'a>(&self, model: &'a WordVector) -> Result<HnswIndex<'a>, Word2VecError> {
+        if self.m < 2 {
+            return Err(Word2VecError::InvalidParameter(format!(
+                "M must be at least 2, not {}",
+                self.m
+            )));
+        }
+        if self.ef_construction == 0 {
+            return Err(Word2VecError::InvalidParameter(
+                "ef_construction must be positive".to_string(),
+            ));
+        }
+        if model.word_count() > u32::MAX as usize {
+            return Err(Word2VecError::InvalidParameter(format!(
+                "{} words exceed the {} rows the links can refer to",
+                model.word_count(),
+                u32::MAX
+            )));
+        }
+        let mut index = HnswIndex {
+            model,
+            m: self.m,
+            ef_construction: self.ef_construction,
+            ef_search: self.ef_construction.min(64).max(self.m),
+            entry_point: None,
+            links: Vec::with_capacity(model.word_count()),
+        };
+        let level_factor = 1.0 / (self.m as f64).ln();
+        let mut rng = Rng::new(self.seed);
+        for row in 0..model.word_count() {
+            // uniform in (0, 1], so that the logarithm is finite
+            let uniform = ((rng.next_u64() >> 11) + 1) as f64 / (1u64 << 53) as f64;
+            let level = ((-uniform.ln() * level_factor) as usize).min(MAX_LEVEL);
+            index.insert(row, level);
+        }
+        Ok(index)
+    }
+}
+
+/// Approximate nearest neighbor index over the words of a
+/// [`WordVector`](../wordvectors/struct.WordVector.html)
+pub struct HnswIndex<'a> {
+    model: &'a WordVector,
+    m: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    entry_point: Option<usize>,
+    /// The neighbors of each row, per layer from the bottom up
+    links: Vec<Vec<Vec<u32>>>,
+}
+
+impl<'a> HnswIndex<'a> {
+    /// Set the number of candidates tracked while searching
+    ///
+    /// Higher values find the true nearest neighbors more often but take longer. At least `n`
+    /// candidates are tracked when asking for `n` words.
+    pub fn ef_search(mut self, ef_search: usize) -> HnswIndex<'a> {
+        self.ef_search = ef_search;
+        self
+    }
+
+    fn similarity(&self, row: usize, query: &[f32]) -> f32 {
+        utils::dot_product(&self.model.vector(row), query)
+    }
+
+    fn max_connections(&self, layer: usize) -> usize {
+        if layer == 0 {
+            2 * self.m
+        } else {
+            self.m
+        }
+    }
+
+    /// The `ef` rows of `layer` closest to `query` reachable from `entry_points`, best first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: &[Candidate],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry_points.iter().map(|c| c.index).collect();
+        let mut candidates: BinaryHeap<Candidate> = entry_points.iter().cloned().collect();
+        let mut results: BinaryHeap<Reverse<Candidate>> =
+            entry_points.iter().cloned().map(Reverse).collect();
+        while let Some(candidate) = candidates.pop() {
+            let worst = results.peek().map_or(f32::NEG_INFINITY, |r| r.0.similarity);
+            if candidate.similarity < worst && results.len() >= ef {
+                break;
+            }
+            for &neighbor in &self.links[candidate.index][layer] {
+                let neighbor = neighbor as usize;
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let similarity = self.similarity(neighbor, query);
+                let worst = results.peek().map_or(f32::NEG_INFINITY, |r| r.0.similarity);
+                if results.len() < ef || similarity > worst {
+                    let neighbor = Candidate {
+                        similarity,
+                        index: neighbor,
+                    };
+                    candidates.push(neighbor);
+                    results.push(Reverse(neighbor));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+        let mut results: Vec<Candidate> = results.into_iter().map(|r| r.0).collect();
+        results.sort_by(|a, b| b.cmp(a));
+        results.truncate(ef);
+        results
+    }
+
+    /// Pick up to `m` of `candidates`, sorted best first, preferring ones which are closer to
+    /// the query than to any neighbor picked before, so that the links spread out.
+    fn select_neighbors(&self, candidates: &[Candidate], m: usize) -> Vec<u32> {
+        let mut selected: Vec<Candidate> = Vec::with_capacity(m);
+        let mut pruned: Vec<Candidate> = Vec::new();
+        for &candidate in candidates {
+            if selected.len() >= m {
+                break;
+            }
+            let vector = self.model.vector(candidate.index);
+            if selected
+                .iter()
+                .all(|s| self.similarity(s.index, &vector) < candidate.similarity)
+            {
+                selected.push(candidate);
+            } else {
+                pruned.push(candidate);
+            }
+        }
+        let missing = m.saturating_sub(selected.len());
+        selected.extend(pruned.into_iter().take(missing));
+        selected.into_iter().map(|c| c.index as u32).collect()
+    }
+
+    fn insert(&mut self, row: usize, level: usize) {
+        self.links.push(vec![Vec::new(); level + 1]);
+        let entry_point = match self.entry_point {
+            Some(entry_point) => entry_point,
+            None => {
+                self.entry_point = Some(row);
+                return;
+            }
+        };
+        let query = self.model.vector(row);
+        let top = self.links[entry_point].len() - 1;
+        let mut entry_points = vec![Candidate {
+            similarity: self.similarity(entry_point, &query),
+            index: entry_point,
+        }];
+        for layer in (level + 1..=top).rev() {
+            entry_points = self.search_layer(&query, &entry_points, 1, layer);
+        }
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&query, &entry_points, self.ef_construction, layer);
+            let neighbors = self.select_neighbors(&found, self.m);
+            for &neighbor in &neighbors {
+                let neighbor = neighbor as usize;
+                self.links[neighbor][layer].push(row as u32);
+                if self.links[neighbor][layer].len() > self.max_connections(layer) {
+                    let vector = self.model.vector(neighbor);
+                    let mut candidates: Vec<Candidate> = self.links[neighbor][layer]
+                        .iter()
+                        .map(|&index| Candidate {
+                            similarity: self.similarity(index as usize, &vector),
+                            index: index as usize,
+                        })
+                        .collect();
+                    candidates.sort_by(|a, b| b.cmp(a));
+                    self.links[neighbor][layer] =
+                        self.select_neighbors(&candidates, self.max_connections(layer));
+                }
+            }
+            self.links[row][layer] = neighbors;
+            entry_points = found;
+        }
+        if level > top {
+            self.entry_point = Some(row);
+        }
+    }
+
+    /// Find approximately the `n` words closest to `word`
+    ///
+    /// Returns `None` if `word` is unknown. The result has the same form as
+    /// [`WordVector::cosine`](../wordvectors/struct.WordVector.html#method.cosine).
+    pub fn most_similar(&self, word: &str, n: usize) -> Option<Vec<(String, f32)>> {
+        let row = self.model.index_of(word)?;
+        let query = self.model.vector(row);
+        let entry_point = match self.entry_point {
+            Some(entry_point) => entry_point,
+            None => return Some(Vec::new()),
+        };
+        let mut entry_points = vec![Candidate {
+            similarity: self.similarity(entry_point, &query),
+            index: entry_point,
+        }];
+        for layer in (1..self.links[entry_point].len()).rev() {
+            entry_points = self.search_layer(&query, &entry_points, 1, layer);
+        }
+        let ef = self.ef_search.max(n + 1);
+        let found = self.search_layer(&query, &entry_points, ef, 0);
+        Some(
+            found
+                .into_iter()
+                .filter(|c| c.index != row)
+                .take(n)
+                .map(|c| (self.model.word(c.index).to_string(), c.similarity))
+                .collect(),
+        )
+    }
+
+    /// Store the graph of the index in a file
+    pub fn save(&self, file_name: &str) -> Result<(), Word2VecError> {
+        let file = File::create(file_name)?;
+        self.write(BufWriter::new(file))
+    }
+
+    /// Write the graph of the index to a writer
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Word2VecError> {
+        writer.write_all(MAGIC)?;
+        writer.write_u32::<LittleEndian>(VERSION)?;
+        writer.write_u32::<LittleEndian>(0)?;
+        for &value in &[
+            self.links.len(),
+            self.model.get_col_count(),
+            self.m,
+            self.ef_construction,
+            self.ef_search,
+        ] {
+            writer.write_u64::<LittleEndian>(value as u64)?;
+        }
+        writer.write_u64::<LittleEndian>(self.entry_point.map_or(u64::MAX, |e| e as u64))?;
+        for layers in &self.links {
+            writer.write_u32::<LittleEndian>(layers.len() as u32)?;
+            for neighbors in layers {
+                writer.write_u32::<LittleEndian>(neighbors.len() as u32)?;
+                for &neighbor in neighbors {
+                    writer.write_u32::<LittleEndian>(neighbor)?;
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load an index stored by [`save`](#method.save) for the `model` it was built from
+    pub fn load(file_name: &str, model: &'a WordVector) -> Result<HnswIndex<'a>, Word2VecError> {
+        let file = File::open(file_name)?;
+        HnswIndex::load_from_reader(BufReader::new(file), model)
+    }
+
+    /// Load an index written by [`write`](#method.write) from a reader
+    ///
+    /// The vocabulary and vector size recorded in the index have to match `model`.
+    pub fn load_from_reader<R: BufRead>(
+        mut reader: R,
+        model: &'a WordVector,
+    ) -> Result<HnswIndex<'a>, Word2VecError> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("missing magic bytes"));
+        }
+        if reader.read_u32::<LittleEndian>()? != VERSION {
+            return Err(invalid("unsupported format version"));
+        }
+        reader.read_u32::<LittleEndian>()?;
+        let mut header = [0u64; 6];
+        for value in header.iter_mut() {
+            *value = reader.read_u64::<LittleEndian>()?;
+        }
+        let [vocabulary_size, vector_size, m, ef_construction, ef_search, entry_point] = header;
+        if vocabulary_size != model.word_count() as u64
+            || vector_size != model.get_col_count() as u64
+        {
+            return Err(invalid("index was built for a different model"));
+        }
+        let entry_point = match entry_point {
+            u64::MAX => None,
+            entry_point if entry_point < vocabulary_size => Some(entry_point as usize),
+            _ => return Err(invalid("entry point is out of range")),
+        };
+
+        let mut links = Vec::with_capacity(model.word_count());
+        for _ in 0..vocabulary_size {
+            let layer_count = reader.read_u32::<LittleEndian>()? as usize;
+            if layer_count == 0 || layer_count > MAX_LEVEL + 1 {
+                return Err(invalid("invalid number of layers"));
+            }
+            let mut layers = Vec::with_capacity(layer_count);
+            for _ in 0..layer_count {
+                let count = reader.read_u32::<LittleEndian>()?;
+                let mut neighbors = Vec::new();
+                for _ in 0..count {
+                    let neighbor = reader.read_u32::<LittleEndian>()?;
+                    if u64::from(neighbor) >= vocabulary_size {
+                        return Err(invalid("neighbor is out of range"));
+                    }
+                    neighbors.push(neighbor);
+                }
+                layers.push(neighbors);
+            }
+            links.push(layers);
+        }
+        // every neighbor has to exist on the layer it is linked on
+        for layers in &links {
+            for (layer, neighbors) in layers.iter().enumerate() {
+                if neighbors.iter().any(|&n| links[n as usize].len() <= layer) {
+                    return Err(invalid("neighbor is missing from its layer"));
+                }
+            }
+        }
+        if let Some(entry_point) = entry_point {
+            if links
+                .iter()
+                .any(|layers| layers.len() > links[entry_point].len())
+            {
+                return Err(invalid("entry point is not on the top layer"));
+            }
+        }
+
+        Ok(HnswIndex {
+            model,
+            m: m as usize,
+            ef_construction: ef_construction as usize,
+            ef_search: ef_search as usize,
+            entry_point,
+            links,
+        })
+    }
+}
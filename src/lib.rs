@@ -1,10 +1,32 @@
 #![feature(test)]
-#![feature(portable_simd)]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
 extern crate byteorder;
+#[cfg(feature = "bzip2")]
+extern crate bzip2;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "half")]
+extern crate half;
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "parallel")]
+extern crate rayon;
 extern crate test;
+#[cfg(feature = "xz")]
+extern crate xz2;
 
+mod compression;
 pub mod errors;
+pub mod evaluation;
+pub mod hnsw;
+pub mod loader;
+#[cfg(feature = "mmap")]
+pub mod mappedvectors;
+mod matrix;
+pub mod pqvectors;
+mod search;
 mod utils;
 pub mod vectorreader;
+pub mod vectorwriter;
 pub mod wordclusters;
 pub mod wordvectors;
@@ -0,0 +1,340 @@
+//! Configurable loading of word vector spaces
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+
+use crate::compression;
+use crate::errors::Word2VecError;
+use crate::matrix::Matrix;
+use crate::utils;
+use crate::vectorreader::{
+    self, ElementType, Endianness, GloveReader, RecordReader, TextWordVectorReader, Utf8Policy,
+    VectorFormat, WordVectorReader,
+};
+use crate::wordvectors::{Precision, WordVector};
+
+/// Predicate deciding whether a word is loaded
+type WordFilter = Box<dyn Fn(&str) -> bool>;
+
+/// Maximum number of components allocated before the records are read
+///
+/// The vocabulary size of the header is only a claim; a truncated or corrupt file must fail with
+/// an error about its records rather than exhaust memory up front.
+const MAX_PREALLOCATED_COMPONENTS: usize = 1 << 24;
+
+/// Builder for loading a [`WordVector`](../wordvectors/struct.WordVector.html) with options
+///
+/// The options restrict which words are loaded. They are applied while streaming through the
+/// file, so the vectors of skipped words are never decoded nor allocated.
+///
+/// ```no_run
+/// use word2vec::wordvectors::WordVector;
+///
+/// let model = WordVector::loader()
+///     .max_words(200_000)
+///     .filter(|word| !word.contains('_'))
+///     .load_from_binary("vectors.bin")
+///     .expect("Unable to load word vector model");
+/// ```
+#[derive(Default)]
+pub struct WordVectorLoader {
+    max_words: Option<usize>,
+    filter: Option<WordFilter>,
+    allow_list: Option<HashSet<String>>,
+    keep_raw_vectors: bool,
+    keep_norms: bool,
+    strict: bool,
+    utf8_policy: Utf8Policy,
+    element_type: ElementType,
+    endianness: Endianness,
+    precision: Precision,
+}
+
+impl WordVectorLoader {
+    pub fn new() -> WordVectorLoader {
+        WordVectorLoader::default()
+    }
+
+    /// Stop loading after `max_words` words have been accepted
+    ///
+    /// Word2vec files are sorted by frequency, so this keeps the most frequent words which pass
+    /// the other options.
+    pub fn max_words(mut self, max_words: usize) -> WordVectorLoader {
+        self.max_words = Some(max_words);
+        self
+    }
+
+    /// Only load words for which `filter` returns `true`.
+    pub fn filter<F: Fn(&str) -> bool + 'static>(mut self, filter: F) -> WordVectorLoader {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Only load words contained in `words`
+    ///
+    /// Loading stops as soon as all of the given words have been found.
+    pub fn allow_list(mut self, words: HashSet<String>) -> WordVectorLoader {
+        self.allow_list = Some(words);
+        self
+    }
+
+    /// Keep a copy of the vectors as stored in the file
+    ///
+    /// The similarity queries use normalized vectors, which loses the original length of each
+    /// vector. With this option the unnormalized vectors are available through
+    /// [`WordVector::get_vector_raw`](../wordvectors/struct.WordVector.html#method.get_vector_raw),
+    /// at the cost of twice the memory. The norms are kept as well.
+    pub fn keep_raw_vectors(mut self, keep: bool) -> WordVectorLoader {
+        self.keep_raw_vectors = keep;
+        self
+    }
+
+    /// Keep the length of each vector before normalization
+    ///
+    /// The norms are available through
+    /// [`WordVector::get_norm`](../wordvectors/struct.WordVector.html#method.get_norm) and take
+    /// a single `f32` per word.
+    pub fn keep_norms(mut self, keep: bool) -> WordVectorLoader {
+        self.keep_norms = keep;
+        self
+    }
+
+    /// Validate the whole file while loading
+    ///
+    /// In strict mode, loading fails if data follows the records declared in the header, if a
+    /// vector contains NaN or infinite components, or if a text record has more components than
+    /// declared. The records which are not loaded due to the other options are validated as
+    /// well, so the whole file is read.
+    pub fn strict(mut self, strict: bool) -> WordVectorLoader {
+        self.strict = strict;
+        self
+    }
+
+    /// Set how words which are not valid UTF-8 are handled
+    ///
+    /// By default, such words make loading fail. With `Utf8Policy::Raw`, these words are only
+    /// found by their original bytes, see
+    /// [`WordVector::index_of_bytes`](../wordvectors/struct.WordVector.html#method.index_of_bytes),
+    /// while the filter options and the saving functions see the lossily decoded words.
+    pub fn utf8_policy(mut self, policy: Utf8Policy) -> WordVectorLoader {
+        self.utf8_policy = policy;
+        self
+    }
+
+    /// Set the type of the vector components of binary files
+    ///
+    /// Format detection by [`load`](#method.load) expects `f32` components, so files with
+    /// `f64` components should be loaded with [`load_from_binary`](#method.load_from_binary).
+    pub fn element_type(mut self, element_type: ElementType) -> WordVectorLoader {
+        self.element_type = element_type;
+        self
+    }
+
+    /// Set the byte order of the vector components of binary files.
+    pub fn endianness(mut self, endianness: Endianness) -> WordVectorLoader {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Set the precision in which the vectors are kept in memory
+    ///
+    /// Each vector is converted after it has been read and normalized, so the file is never
+    /// held in memory in full precision. The exception is `Precision::Int8(Scale::PerDimension)`,
+    /// whose scales depend on all vectors, so they are quantized once loading completes.
+    pub fn precision(mut self, precision: Precision) -> WordVectorLoader {
+        self.precision = precision;
+        self
+    }
+
+    /// Load a word vector space from a file of unknown format, see
+    /// [`WordVector::load`](../wordvectors/struct.WordVector.html#method.load).
+    pub fn load(&self, file_name: &str) -> Result<WordVector, Word2VecError> {
+        let reader = compression::open_file(file_name)?;
+        let (format, reader) = vectorreader::detect_format(reader)?;
+
+        match format {
+            VectorFormat::Binary => self.load_from_reader(reader),
+            VectorFormat::Text => self.load_from_text_reader(reader),
+            VectorFormat::Glove => self.load_from_glove_reader(reader),
+        }
+    }
+
+    /// Load a word vector space from a file in binary format, see
+    /// [`WordVector::load_from_binary`](../wordvectors/struct.WordVector.html#method.load_from_binary).
+    pub fn load_from_binary(&self, file_name: &str) -> Result<WordVector, Word2VecError> {
+        let reader = compression::open_file(file_name)?;
+
+        self.load_from_reader(reader)
+    }
+
+    /// Load a word vector space in binary format from a reader.
+    pub fn load_from_reader<R: BufRead>(&self, reader: R) -> Result<WordVector, Word2VecError> {
+        let reader = WordVectorReader::new_from_reader(reader)?
+            .strict(self.strict)
+            .utf8_policy(self.utf8_policy)
+            .element_type(self.element_type)
+            .endianness(self.endianness);
+        let vocabulary_size = reader.vocabulary_size();
+
+        self.load_records(reader, vocabulary_size)
+    }
+
+    /// Load a word vector space from a file in text format, see
+    /// [`WordVector::load_from_text`](../wordvectors/struct.WordVector.html#method.load_from_text).
+    pub fn load_from_text(&self, file_name: &str) -> Result<WordVector, Word2VecError> {
+        let reader = compression::open_file(file_name)?;
+
+        self.load_from_text_reader(reader)
+    }
+
+    /// Load a word vector space in text format from a reader.
+    pub fn load_from_text_reader<R: BufRead>(
+        &self,
+        reader: R,
+    ) -> Result<WordVector, Word2VecError> {
+        let reader = TextWordVectorReader::new_from_reader(reader)?
+            .strict(self.strict)
+            .utf8_policy(self.utf8_policy);
+        let vocabulary_size = reader.vocabulary_size();
+
+        self.load_records(reader, vocabulary_size)
+    }
+
+    /// Load a word vector space from a file in GloVe format, see
+    /// [`WordVector::load_from_glove`](../wordvectors/struct.WordVector.html#method.load_from_glove).
+    pub fn load_from_glove(&self, file_name: &str) -> Result<WordVector, Word2VecError> {
+        let reader = compression::open_file(file_name)?;
+
+        self.load_from_glove_reader(reader)
+    }
+
+    /// Load a word vector space in GloVe format from a reader.
+    pub fn load_from_glove_reader<R: BufRead>(
+        &self,
+        reader: R,
+    ) -> Result<WordVector, Word2VecError> {
+        let reader = GloveReader::new_from_reader(reader)?
+            .strict(self.strict)
+            .utf8_policy(self.utf8_policy);
+
+        self.load_records(reader, 0)
+    }
+
+    fn accepts(&self, word: &str) -> bool {
+        if let Some(ref allow_list) = self.allow_list {
+            if !allow_list.contains(word) {
+                return false;
+            }
+        }
+        match self.filter {
+            Some(ref filter) => filter(word),
+            None => true,
+        }
+    }
+
+    fn is_complete(&self, word_count: usize) -> bool {
+        self.max_words.is_some_and(|max| word_count >= max)
+            || self
+                .allow_list
+                .as_ref()
+                .is_some_and(|words| word_count >= words.len())
+    }
+
+    /// Stream the records of `reader` into a vector space, normalizing each vector.
+    ///
+    /// If a word occurs more than once, the last vector wins but the word keeps the row of its
+    /// first occurrence. Errors of the reader abort loading.
+    fn load_records<T: RecordReader>(
+        &self,
+        mut reader: T,
+        vocabulary_size: usize,
+    ) -> Result<WordVector, Word2VecError> {
+        let vector_size = reader.vector_size();
+        let capacity = match self.allow_list {
+            Some(ref words) => vocabulary_size.min(words.len()),
+            None => vocabulary_size.min(self.max_words.unwrap_or(usize::MAX)),
+        }
+        .min(MAX_PREALLOCATED_COMPONENTS / vector_size.max(1));
+        let mut words: Vec<String> = Vec::with_capacity(capacity);
+        let mut indices: HashMap<String, usize> = HashMap::with_capacity(capacity);
+        let mut byte_indices: HashMap<Vec<u8>, usize> = HashMap::new();
+        let mut matrix = Matrix::with_capacity(self.precision, vector_size, capacity);
+        let mut raw: Option<Vec<f32>> = if self.keep_raw_vectors {
+            Some(Vec::with_capacity(capacity * vector_size))
+        } else {
+            None
+        };
+        let mut norms: Option<Vec<f32>> = if self.keep_raw_vectors || self.keep_norms {
+            Some(Vec::with_capacity(capacity))
+        } else {
+            None
+        };
+        // buffer for the vector of the current record
+        let mut vector = vec![0f32; vector_size];
+
+        while !self.is_complete(words.len()) {
+            let word = match reader.read_word()? {
+                Some(word) => word,
+                None => break,
+            };
+            if !self.accepts(&word) {
+                if self.strict {
+                    reader.read_vector(&mut vector)?;
+                } else {
+                    reader.skip_vector()?;
+                }
+                continue;
+            }
+
+            // words which are not valid UTF-8 are told apart by their bytes
+            let raw_word = reader.raw_word().map(|bytes| bytes.to_vec());
+            let row = match raw_word {
+                Some(ref bytes) => byte_indices.get(bytes),
+                None => indices.get(&word),
+            };
+            let row = row.cloned();
+            reader.read_vector(&mut vector)?;
+            match row {
+                Some(row) => {
+                    if let Some(ref mut raw) = raw {
+                        raw[row * vector_size..(row + 1) * vector_size].copy_from_slice(&vector);
+                    }
+                    let norm = utils::vector_norm(&mut vector);
+                    if let Some(ref mut norms) = norms {
+                        norms[row] = norm;
+                    }
+                    matrix.set_row(row, &vector);
+                }
+                None => {
+                    if let Some(ref mut raw) = raw {
+                        raw.extend_from_slice(&vector);
+                    }
+                    let norm = utils::vector_norm(&mut vector);
+                    if let Some(ref mut norms) = norms {
+                        norms.push(norm);
+                    }
+                    matrix.push_row(&vector);
+                    match raw_word {
+                        Some(bytes) => byte_indices.insert(bytes, words.len()),
+                        None => indices.insert(word.clone(), words.len()),
+                    };
+                    words.push(word);
+                }
+            }
+        }
+        if self.strict {
+            while reader.read_word()?.is_some() {
+                reader.read_vector(&mut vector)?;
+            }
+        }
+
+        Ok(WordVector::from_parts(
+            words,
+            indices,
+            byte_indices,
+            matrix.finish(),
+            raw,
+            norms,
+            vector_size,
+        ))
+    }
+}
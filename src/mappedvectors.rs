@@ -0,0 +1,348 @@
+//! Memory-mapped word vector spaces
+//!
+//! Loading a large model with [`WordVector`](../wordvectors/struct.WordVector.html) parses the
+//! whole file and allocates every vector. A [`MappedWordVector`](struct.MappedWordVector.html)
+//! instead maps a file in a crate-specific layout into memory and uses it in place, so opening
+//! it is instant and all processes using the same file share the page cache.
+//!
+//! The layout (all integers little-endian) is:
+//!
+//! | offset | content |
+//! |---|---|
+//! | 0 | magic `W2VMMAP\0` |
+//! | 8 | format version (`u32`), reserved (`u32`) |
+//! | 16 | vocabulary size `V` (`u64`) |
+//! | 24 | vector size `D` (`u64`) |
+//! | 32 | offset of the word offsets table (`u64`) |
+//! | 40 | offset of the sorted word index (`u64`) |
+//! | 48 | offset of the word data (`u64`) |
+//! | 56 | offset of the vector matrix (`u64`), a multiple of 64 |
+//!
+//! The word offsets table holds `V + 1` `u64` offsets into the word data, where word `i` spans
+//! the UTF-8 bytes from entry `i` to entry `i + 1`. The sorted word index holds `V` `u32` row
+//! numbers ordered by the bytes of their words and allows looking up words by binary search.
+//! The matrix contains `V` rows of `D` normalized `f32` components.
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::mem;
+use std::slice;
+use std::str;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use memmap2::Mmap;
+
+use crate::errors::Word2VecError;
+use crate::search::{self, AnalogyMethod, AnalogyResult, VectorSpace};
+use crate::utils;
+use crate::wordvectors::WordVector;
+
+const MAGIC: &[u8; 8] = b"W2VMMAP\0";
+const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 64;
+const MATRIX_ALIGNMENT: u64 = 64;
+
+fn align(offset: u64, alignment: u64) -> u64 {
+    offset.div_ceil(alignment) * alignment
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_le_bytes(buf)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
+fn invalid(reason: &str) -> Word2VecError {
+    Word2VecError::InvalidMappedFile(reason.to_string())
+}
+
+/// Word vector space backed by a memory-mapped file
+pub struct MappedWordVector {
+    mmap: Mmap,
+    vocabulary_size: usize,
+    vector_size: usize,
+    offsets_offset: usize,
+    sorted_offset: usize,
+    words: (usize, usize),
+    matrix_offset: usize,
+}
+
+impl MappedWordVector {
+    /// Store a word vector space in the memory-mappable layout
+    pub fn save(model: &WordVector, file_name: &str) -> Result<(), Word2VecError> {
+        let file = File::create(file_name)?;
+        MappedWordVector::write(model, BufWriter::new(file))
+    }
+
+    /// Write a word vector space in the memory-mappable layout to a writer
+    pub fn write<W: Write>(model: &WordVector, mut writer: W) -> Result<(), Word2VecError> {
+        let words: Vec<&String> = model.get_words().collect();
+        if words.len() > u32::MAX as usize {
+            return Err(invalid("too many words for the sorted index"));
+        }
+        let vocabulary_size = words.len() as u64;
+        let vector_size = model.get_col_count() as u64;
+
+        let offsets_offset = HEADER_SIZE;
+        let sorted_offset = offsets_offset + 8 * (vocabulary_size + 1);
+        let words_offset = sorted_offset + 4 * vocabulary_size;
+        let words_len: u64 = words.iter().map(|w| w.len() as u64).sum();
+        let matrix_offset = align(words_offset + words_len, MATRIX_ALIGNMENT);
+
+        writer.write_all(MAGIC)?;
+        writer.write_u32::<LittleEndian>(VERSION)?;
+        writer.write_u32::<LittleEndian>(0)?;
+        for value in &[
+            vocabulary_size,
+            vector_size,
+            offsets_offset,
+            sorted_offset,
+            words_offset,
+            matrix_offset,
+        ] {
+            writer.write_u64::<LittleEndian>(*value)?;
+        }
+
+        let mut offset = 0u64;
+        writer.write_u64::<LittleEndian>(offset)?;
+        for word in &words {
+            offset += word.len() as u64;
+            writer.write_u64::<LittleEndian>(offset)?;
+        }
+
+        let mut sorted: Vec<u32> = (0..words.len() as u32).collect();
+        sorted.sort_by(|&a, &b| {
+            words[a as usize]
+                .as_bytes()
+                .cmp(words[b as usize].as_bytes())
+        });
+        for index in sorted {
+            writer.write_u32::<LittleEndian>(index)?;
+        }
+
+        for word in &words {
+            writer.write_all(word.as_bytes())?;
+        }
+        let padding = matrix_offset - (words_offset + words_len);
+        writer.write_all(&vec![0u8; padding as usize])?;
+
+        for &x in model.matrix().iter() {
+            writer.write_f32::<LittleEndian>(x)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Map a file in the layout written by [`save`](#method.save) into memory
+    ///
+    /// The file must not be modified while it is mapped.
+    pub fn open(file_name: &str) -> Result<MappedWordVector, Word2VecError> {
+        if cfg!(target_endian = "big") {
+            return Err(invalid(
+                "memory-mapped vectors require a little-endian target",
+            ));
+        }
+        let file = File::open(file_name)?;
+        // Safety: the mapping is read-only and the file is required to stay unmodified.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let len = mmap.len() as u64;
+        if len < HEADER_SIZE || &mmap[..8] != MAGIC {
+            return Err(invalid("missing magic bytes"));
+        }
+        if read_u32(&mmap, 8) != VERSION {
+            return Err(invalid("unsupported format version"));
+        }
+
+        let vocabulary_size = read_u64(&mmap, 16);
+        let vector_size = read_u64(&mmap, 24);
+        let offsets_offset = read_u64(&mmap, 32);
+        let sorted_offset = read_u64(&mmap, 40);
+        let words_offset = read_u64(&mmap, 48);
+        let matrix_offset = read_u64(&mmap, 56);
+
+        let fits = |offset: u64, size: Option<u64>| match size {
+            Some(size) => offset.checked_add(size).is_some_and(|end| end <= len),
+            None => false,
+        };
+        let matrix_size = vocabulary_size
+            .checked_mul(vector_size)
+            .and_then(|n| n.checked_mul(mem::size_of::<f32>() as u64));
+        if !fits(
+            offsets_offset,
+            vocabulary_size
+                .checked_add(1)
+                .and_then(|n| n.checked_mul(8)),
+        ) || !fits(sorted_offset, vocabulary_size.checked_mul(4))
+            || !fits(matrix_offset, matrix_size)
+            || !matrix_offset.is_multiple_of(MATRIX_ALIGNMENT)
+        {
+            return Err(invalid("sections exceed the file size"));
+        }
+        let words_len = read_u64(&mmap, (offsets_offset + 8 * vocabulary_size) as usize);
+        if !fits(words_offset, Some(words_len)) {
+            return Err(invalid("word data exceeds the file size"));
+        }
+        let words = (words_offset as usize, (words_offset + words_len) as usize);
+        let text = match str::from_utf8(&mmap[words.0..words.1]) {
+            Ok(text) => text,
+            Err(_) => return Err(invalid("words are not valid UTF-8")),
+        };
+        let mut previous = 0;
+        for index in 0..=vocabulary_size as usize {
+            let offset = read_u64(&mmap, offsets_offset as usize + 8 * index);
+            if offset < previous || !text.is_char_boundary(offset as usize) {
+                return Err(invalid(
+                    "word offsets are not ascending character boundaries",
+                ));
+            }
+            previous = offset;
+        }
+        for index in 0..vocabulary_size as usize {
+            if u64::from(read_u32(&mmap, sorted_offset as usize + 4 * index)) >= vocabulary_size {
+                return Err(invalid("sorted word index refers to a missing row"));
+            }
+        }
+
+        Ok(MappedWordVector {
+            mmap,
+            vocabulary_size: vocabulary_size as usize,
+            vector_size: vector_size as usize,
+            offsets_offset: offsets_offset as usize,
+            sorted_offset: sorted_offset as usize,
+            words,
+            matrix_offset: matrix_offset as usize,
+        })
+    }
+
+    fn matrix(&self) -> &[f32] {
+        let bytes = &self.mmap[self.matrix_offset..];
+        // Safety: `open` checked that the matrix lies within the mapping and starts at a
+        // 64-byte boundary of the page-aligned mapping, so it is suitably aligned for f32.
+        unsafe {
+            slice::from_raw_parts(
+                bytes.as_ptr() as *const f32,
+                self.vocabulary_size * self.vector_size,
+            )
+        }
+    }
+
+    fn row(&self, index: usize) -> &[f32] {
+        &self.matrix()[index * self.vector_size..(index + 1) * self.vector_size]
+    }
+
+    /// Get word vector for the given word.
+    pub fn get_vector(&self, word: &str) -> Option<&[f32]> {
+        self.index_of(word).map(|index| self.row(index))
+    }
+
+    /// Compute cosine distance to similar words.
+    ///
+    /// See [`WordVector::cosine`](../wordvectors/struct.WordVector.html#method.cosine).
+    pub fn cosine(&self, word: &str, n: usize) -> Option<Vec<(String, f32)>> {
+        search::cosine(self, word, n, self.vocabulary_size)
+    }
+
+    /// Find the words closest to the mean of the `pos` vectors and the negated `neg` vectors.
+    ///
+    /// See [`WordVector::analogy`](../wordvectors/struct.WordVector.html#method.analogy).
+    pub fn analogy(
+        &self,
+        pos: Vec<&str>,
+        neg: Vec<&str>,
+        n: usize,
+    ) -> Result<AnalogyResult, Word2VecError> {
+        search::analogy(
+            self,
+            AnalogyMethod::CosAdd,
+            &pos,
+            &neg,
+            n,
+            self.vocabulary_size,
+        )
+    }
+
+    /// Get the number of all known words from the vocabulary.
+    pub fn word_count(&self) -> usize {
+        self.vocabulary_size
+    }
+
+    /// Return the number of columns of the word vector.
+    pub fn get_col_count(&self) -> usize {
+        self.vector_size
+    }
+
+    /// Get all known words from the vocabulary, in the order of the file.
+    pub fn get_words(&self) -> MappedWords<'_> {
+        MappedWords {
+            model: self,
+            index: 0,
+        }
+    }
+}
+
+impl VectorSpace for MappedWordVector {
+    fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    fn rows(&self) -> usize {
+        self.vocabulary_size
+    }
+
+    fn vector(&self, index: usize) -> Cow<'_, [f32]> {
+        Cow::Borrowed(self.row(index))
+    }
+
+    fn dots(&self, query: &[f32], rows: usize) -> Vec<f32> {
+        utils::map_rows(rows.min(self.vocabulary_size), |index| {
+            utils::dot_product(self.row(index), query)
+        })
+    }
+
+    fn word(&self, index: usize) -> &str {
+        let start = read_u64(&self.mmap, self.offsets_offset + 8 * index) as usize;
+        let end = read_u64(&self.mmap, self.offsets_offset + 8 * (index + 1)) as usize;
+        // Safety: `open` checked that the word data is valid UTF-8 and that the offsets are
+        // ascending character boundaries within it.
+        unsafe { str::from_utf8_unchecked(&self.mmap[self.words.0 + start..self.words.0 + end]) }
+    }
+
+    fn index_of(&self, word: &str) -> Option<usize> {
+        let (mut low, mut high) = (0, self.vocabulary_size);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let index = read_u32(&self.mmap, self.sorted_offset + 4 * middle) as usize;
+            match self.word(index).as_bytes().cmp(word.as_bytes()) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => return Some(index),
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the words of a [`MappedWordVector`](struct.MappedWordVector.html)
+pub struct MappedWords<'parent> {
+    model: &'parent MappedWordVector,
+    index: usize,
+}
+
+impl<'a> Iterator for MappedWords<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.model.vocabulary_size {
+            return None;
+        }
+        self.index += 1;
+        Some(self.model.word(self.index - 1))
+    }
+}
@@ -0,0 +1,272 @@
+//! Row-major storage of the vectors of a word vector space in different precisions
+use std::borrow::Cow;
+
+#[cfg(feature = "half")]
+use half::{bf16, f16};
+
+use crate::utils;
+use crate::wordvectors::{Precision, Scale};
+
+enum Storage {
+    F32(Vec<f32>),
+    #[cfg(feature = "half")]
+    F16(Vec<f16>),
+    #[cfg(feature = "half")]
+    Bf16(Vec<bf16>),
+    /// Quantized components with one scale factor per row
+    I8PerVector {
+        data: Vec<i8>,
+        scales: Vec<f32>,
+    },
+    /// Quantized components with one scale factor per column
+    I8PerDimension {
+        data: Vec<i8>,
+        scales: Vec<f32>,
+    },
+}
+
+/// Matrix with one row of `vector_size` components per word
+///
+/// Rows are passed in and out as `f32`; the conversion from and to the storage precision happens
+/// on the fly.
+///
+/// The scales of `Precision::Int8(Scale::PerDimension)` depend on all rows, so such a matrix
+/// keeps its rows as `f32` until [`finish`](#method.finish) quantizes them.
+pub struct Matrix {
+    vector_size: usize,
+    rows: usize,
+    storage: Storage,
+    target: Precision,
+}
+
+impl Matrix {
+    pub fn with_capacity(precision: Precision, vector_size: usize, rows: usize) -> Matrix {
+        let capacity = rows.checked_mul(vector_size).unwrap_or(0);
+        let storage = match precision {
+            Precision::F32 => Storage::F32(Vec::with_capacity(capacity)),
+            #[cfg(feature = "half")]
+            Precision::F16 => Storage::F16(Vec::with_capacity(capacity)),
+            #[cfg(feature = "half")]
+            Precision::Bf16 => Storage::Bf16(Vec::with_capacity(capacity)),
+            Precision::Int8(Scale::PerVector) => Storage::I8PerVector {
+                data: Vec::with_capacity(capacity),
+                scales: Vec::with_capacity(rows),
+            },
+            Precision::Int8(Scale::PerDimension) => Storage::F32(Vec::with_capacity(capacity)),
+        };
+        Matrix {
+            vector_size,
+            rows: 0,
+            storage,
+            target: precision,
+        }
+    }
+
+    /// Bring the matrix into the precision it was created with, once all rows were pushed.
+    pub fn finish(self) -> Matrix {
+        if self.precision() == self.target {
+            self
+        } else {
+            self.convert(self.target)
+        }
+    }
+
+    /// Copy the matrix into `precision`.
+    pub fn convert(&self, precision: Precision) -> Matrix {
+        if let Precision::Int8(Scale::PerDimension) = precision {
+            let components = self.components();
+            let mut scales = vec![0f32; self.vector_size];
+            for row in components.chunks_exact(self.vector_size.max(1)) {
+                for (scale, x) in scales.iter_mut().zip(row) {
+                    *scale = scale.max(x.abs());
+                }
+            }
+            for scale in scales.iter_mut() {
+                *scale /= 127.0;
+            }
+            let data = components
+                .iter()
+                .zip(scales.iter().cycle())
+                .map(|(&x, &scale)| utils::quantize_component(x, scale))
+                .collect();
+            return Matrix {
+                vector_size: self.vector_size,
+                rows: self.rows,
+                storage: Storage::I8PerDimension { data, scales },
+                target: precision,
+            };
+        }
+        let mut matrix = Matrix::with_capacity(precision, self.vector_size, self.rows);
+        for index in 0..self.rows {
+            matrix.push_row(&self.row(index));
+        }
+        matrix
+    }
+
+    pub fn precision(&self) -> Precision {
+        match self.storage {
+            Storage::F32(_) => Precision::F32,
+            #[cfg(feature = "half")]
+            Storage::F16(_) => Precision::F16,
+            #[cfg(feature = "half")]
+            Storage::Bf16(_) => Precision::Bf16,
+            Storage::I8PerVector { .. } => Precision::Int8(Scale::PerVector),
+            Storage::I8PerDimension { .. } => Precision::Int8(Scale::PerDimension),
+        }
+    }
+
+    /// Append `row`, which has `vector_size` components.
+    pub fn push_row(&mut self, row: &[f32]) {
+        self.rows += 1;
+        match self.storage {
+            Storage::F32(ref mut data) => data.extend_from_slice(row),
+            #[cfg(feature = "half")]
+            Storage::F16(ref mut data) => data.extend(row.iter().map(|&x| f16::from_f32(x))),
+            #[cfg(feature = "half")]
+            Storage::Bf16(ref mut data) => data.extend(row.iter().map(|&x| bf16::from_f32(x))),
+            Storage::I8PerVector {
+                ref mut data,
+                ref mut scales,
+            } => scales.push(utils::quantize(row, data)),
+            Storage::I8PerDimension {
+                ref mut data,
+                ref scales,
+            } => data.extend(
+                row.iter()
+                    .zip(scales)
+                    .map(|(&x, &scale)| utils::quantize_component(x, scale)),
+            ),
+        }
+    }
+
+    /// Overwrite row `index` with `row`.
+    pub fn set_row(&mut self, index: usize, row: &[f32]) {
+        let range = index * self.vector_size..(index + 1) * self.vector_size;
+        match self.storage {
+            Storage::F32(ref mut data) => data[range].copy_from_slice(row),
+            #[cfg(feature = "half")]
+            Storage::F16(ref mut data) => {
+                for (x, &y) in data[range].iter_mut().zip(row) {
+                    *x = f16::from_f32(y);
+                }
+            }
+            #[cfg(feature = "half")]
+            Storage::Bf16(ref mut data) => {
+                for (x, &y) in data[range].iter_mut().zip(row) {
+                    *x = bf16::from_f32(y);
+                }
+            }
+            Storage::I8PerVector {
+                ref mut data,
+                ref mut scales,
+            } => {
+                let mut quantized = Vec::with_capacity(row.len());
+                scales[index] = utils::quantize(row, &mut quantized);
+                data[range].copy_from_slice(&quantized);
+            }
+            Storage::I8PerDimension {
+                ref mut data,
+                ref scales,
+            } => {
+                for ((x, &y), &scale) in data[range].iter_mut().zip(row).zip(scales) {
+                    *x = utils::quantize_component(y, scale);
+                }
+            }
+        }
+    }
+
+    /// The components of row `index`, borrowed if they are stored as `f32`.
+    pub fn row(&self, index: usize) -> Cow<'_, [f32]> {
+        let range = index * self.vector_size..(index + 1) * self.vector_size;
+        match self.storage {
+            Storage::F32(ref data) => Cow::Borrowed(&data[range]),
+            #[cfg(feature = "half")]
+            Storage::F16(ref data) => Cow::Owned(data[range].iter().map(|x| x.to_f32()).collect()),
+            #[cfg(feature = "half")]
+            Storage::Bf16(ref data) => Cow::Owned(data[range].iter().map(|x| x.to_f32()).collect()),
+            Storage::I8PerVector {
+                ref data,
+                ref scales,
+            } => Cow::Owned(
+                data[range]
+                    .iter()
+                    .map(|&x| f32::from(x) * scales[index])
+                    .collect(),
+            ),
+            Storage::I8PerDimension {
+                ref data,
+                ref scales,
+            } => Cow::Owned(
+                data[range]
+                    .iter()
+                    .zip(scales)
+                    .map(|(&x, scale)| f32::from(x) * scale)
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The dot products of `query` with the first `rows` rows
+    ///
+    /// Quantized rows are compared with a quantized copy of `query` in integer arithmetic. With the
+    /// `parallel` feature, the rows are scored on all cores.
+    pub fn dots(&self, query: &[f32], rows: usize) -> Vec<f32> {
+        let rows = rows.min(self.rows);
+        let size = self.vector_size;
+        match self.storage {
+            Storage::F32(ref data) => utils::map_rows(rows, |i| {
+                utils::dot_product(&data[i * size..(i + 1) * size], query)
+            }),
+            #[cfg(feature = "half")]
+            Storage::F16(ref data) => utils::map_rows(rows, |i| {
+                utils::dot_product_f16(&data[i * size..(i + 1) * size], query)
+            }),
+            #[cfg(feature = "half")]
+            Storage::Bf16(ref data) => utils::map_rows(rows, |i| {
+                utils::dot_product_bf16(&data[i * size..(i + 1) * size], query)
+            }),
+            Storage::I8PerVector {
+                ref data,
+                ref scales,
+            } => {
+                let mut quantized = Vec::with_capacity(size);
+                let query_scale = utils::quantize(query, &mut quantized);
+                utils::map_rows(rows, |i| {
+                    let dot = utils::dot_product_i8(&data[i * size..(i + 1) * size], &quantized);
+                    dot as f32 * scales[i] * query_scale
+                })
+            }
+            Storage::I8PerDimension {
+                ref data,
+                ref scales,
+            } => {
+                // fold the column scales into the query, so rows need no rescaling
+                let scaled: Vec<f32> = query.iter().zip(scales).map(|(x, s)| x * s).collect();
+                let mut quantized = Vec::with_capacity(size);
+                let query_scale = utils::quantize(&scaled, &mut quantized);
+                utils::map_rows(rows, |i| {
+                    let dot = utils::dot_product_i8(&data[i * size..(i + 1) * size], &quantized);
+                    dot as f32 * query_scale
+                })
+            }
+        }
+    }
+
+    /// All components, row after row.
+    pub fn components(&self) -> Cow<'_, [f32]> {
+        match self.storage {
+            Storage::F32(ref data) => Cow::Borrowed(data),
+            #[cfg(feature = "half")]
+            Storage::F16(ref data) => Cow::Owned(data.iter().map(|x| x.to_f32()).collect()),
+            #[cfg(feature = "half")]
+            Storage::Bf16(ref data) => Cow::Owned(data.iter().map(|x| x.to_f32()).collect()),
+            Storage::I8PerVector { .. } | Storage::I8PerDimension { .. } => {
+                let mut components = Vec::with_capacity(self.rows * self.vector_size);
+                for index in 0..self.rows {
+                    components.extend_from_slice(&self.row(index));
+                }
+                Cow::Owned(components)
+            }
+        }
+    }
+}
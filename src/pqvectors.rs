@@ -0,0 +1,540 @@
+//! Product-quantized word vector spaces
+//!
+//! A [`PqWordVector`](struct.PqWordVector.html) compresses the vectors of a
+//! [`WordVector`](../wordvectors/struct.WordVector.html) with product quantization: each vector
+//! is split into `m` subvectors and every subvector is replaced by the index of the closest of
+//! up to 256 centroids, which are trained with k-means by a
+//! [`ProductQuantizer`](struct.ProductQuantizer.html). A word then takes `m` bytes instead of
+//! `4 * D`: with 300 dimensions and `m = 30`, the codes of 1M words take 30 MB instead of 1.2 GB.
+//!
+//! Queries stay uncompressed. For each query, a table of the dot products of its subvectors with
+//! all centroids is computed once, after which scoring a word only sums `m` table entries
+//! (asymmetric distance computation). The scores approximate the cosine similarity.
+//!
+//! The file layout written by [`save`](struct.PqWordVector.html#method.save) is (all integers
+//! little-endian):
+//!
+//! | content |
+//! |---|
+//! | magic `W2VPQ\0\0\0` |
+//! | format version (`u32`), reserved (`u32`) |
+//! | vocabulary size `V`, vector size `D`, subquantizers `m`, centroids `k` (`u64` each) |
+//! | `V` words, each as its length in bytes (`u32`) followed by its UTF-8 bytes |
+//! | `m * k` centroids of `D / m` `f32` components |
+//! | `V` `f32` norms of the reconstructed vectors |
+//! | `V * m` codes (`u8`) |
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::errors::Word2VecError;
+use crate::search::{self, AnalogyMethod, AnalogyResult, VectorSpace};
+use crate::utils::{self, Rng};
+use crate::wordvectors::{WordVector, Words};
+
+const MAGIC: &[u8; 8] = b"W2VPQ\0\0\0";
+const VERSION: u32 = 1;
+const MAX_CENTROIDS: usize = 256;
+
+fn invalid(reason: &str) -> Word2VecError {
+    Word2VecError::InvalidQuantizedFile(reason.to_string())
+}
+
+fn squared_distance(x: &[f32], y: &[f32]) -> f32 {
+    x.iter().zip(y).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Index of the centroid in `codebook`, rows of `size` components, which is closest to `x`
+fn nearest_centroid(x: &[f32], codebook: &[f32], size: usize) -> usize {
+    let mut best = (0, f32::INFINITY);
+    for (index, centroid) in codebook.chunks_exact(size).enumerate() {
+        let distance = squared_distance(x, centroid);
+        if distance < best.1 {
+            best = (index, distance);
+        }
+    }
+    best.0
+}
+
+/// Cluster `points`, rows of `size` components, into `k` centroids with Lloyd's algorithm.
+fn kmeans(points: &[f32], size: usize, k: usize, iterations: usize, rng: &mut Rng) -> Vec<f32> {
+    let count = points.len() / size;
+    let point = |index: usize| &points[index * size..(index + 1) * size];
+
+    // start from k distinct points, drawn by a partial Fisher-Yates shuffle
+    let mut order: Vec<usize> = (0..count).collect();
+    let mut centroids = Vec::with_capacity(k * size);
+    for i in 0..k {
+        let j = i + rng.below(count - i);
+        order.swap(i, j);
+        centroids.extend_from_slice(point(order[i]));
+    }
+
+    let mut assignments = vec![usize::MAX; count];
+    let mut sums = vec![0f32; k * size];
+    let mut counts = vec![0usize; k];
+    for _ in 0..iterations {
+        let mut changed = false;
+        for (index, assignment) in assignments.iter_mut().enumerate() {
+            let nearest = nearest_centroid(point(index), &centroids, size);
+            if *assignment != nearest {
+                *assignment = nearest;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+
+        sums.iter_mut().for_each(|x| *x = 0.0);
+        counts.iter_mut().for_each(|x| *x = 0);
+        for (index, &assignment) in assignments.iter().enumerate() {
+            counts[assignment] += 1;
+            for (sum, x) in sums[assignment * size..(assignment + 1) * size]
+                .iter_mut()
+                .zip(point(index))
+            {
+                *sum += x;
+            }
+        }
+        for (cluster, &n) in counts.iter().enumerate() {
+            let centroid = &mut centroids[cluster * size..(cluster + 1) * size];
+            if n == 0 {
+                // move an empty cluster to a random point, so that no centroid is wasted
+                centroid.copy_from_slice(point(rng.below(count)));
+            } else {
+                let sum = &sums[cluster * size..(cluster + 1) * size];
+                for (c, s) in centroid.iter_mut().zip(sum) {
+                    *c = s / n as f32;
+                }
+            }
+        }
+    }
+    centroids
+}
+
+/// Builder which trains a [`PqWordVector`](struct.PqWordVector.html) from a word vector space
+///
+/// ```no_run
+/// use word2vec::pqvectors::ProductQuantizer;
+/// use word2vec::wordvectors::WordVector;
+///
+/// let model = WordVector::load_from_binary("vectors.bin").unwrap();
+/// let compressed = ProductQuantizer::new(25).iterations(10).train(&model).unwrap();
+/// compressed.save("vectors.pq").unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ProductQuantizer {
+    subquantizers: usize,
+    centroids: usize,
+    iterations: usize,
+    sample_size: usize,
+    seed: u64,
+}
+
+impl ProductQuantizer {
+    /// Create a quantizer which splits each vector into `subquantizers` subvectors
+    ///
+    /// The vector size of the model must be a multiple of `subquantizers`.
+    pub fn new(subquantizers: usize) -> ProductQuantizer {
+        ProductQuantizer {
+            subquantizers,
+            centroids: MAX_CENTROIDS,
+            iterations: 25,
+            sample_size: 100_000,
+            seed: 0,
+        }
+    }
+
+    /// Set the number of centroids per subquantizer, at most and by default 256.
+    pub fn centroids(mut self, centroids: usize) -> ProductQuantizer {
+        self.centroids = centroids;
+        self
+    }
+
+    /// Set the maximum number of k-means iterations, 25 by default.
+    pub fn iterations(mut self, iterations: usize) -> ProductQuantizer {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Set the number of randomly chosen vectors the codebooks are trained on, 100000 by default
+    ///
+    /// All vectors are encoded, whatever the sample size. The sample size must be positive.
+    pub fn sample_size(mut self, sample_size: usize) -> ProductQuantizer {
+        self.sample_size = sample_size;
+        self
+    }
+
+    /// Set the seed of the random sampling, so that training is reproducible.
+    pub fn seed(mut self, seed: u64) -> ProductQuantizer {
+        self.seed = seed;
+        self
+    }
+
+    /// Train codebooks on the normalized vectors of `model` and encode all of its words.
+    pub fn train(&self, model: &WordVector) -> Result<PqWordVector, Word2VecError> {
+        let vector_size = model.get_col_count();
+        let m = self.subquantizers;
+        if vector_size == 0 {
+            return Err(Word2VecError::InvalidParameter(
+                "vectors without components can't be quantized".to_string(),
+            ));
+        }
+        if m == 0 || !vector_size.is_multiple_of(m) {
+            return Err(Word2VecError::InvalidParameter(format!(
+                "{} subquantizers don't divide the vector size {}",
+                m, vector_size
+            )));
+        }
+        if self.centroids == 0 || self.centroids > MAX_CENTROIDS {
+            return Err(Word2VecError::InvalidParameter(format!(
+                "{} centroids are not within 1 to {}",
+                self.centroids, MAX_CENTROIDS
+            )));
+        }
+        if self.sample_size == 0 {
+            return Err(Word2VecError::InvalidParameter(
+                "codebooks can't be trained on an empty sample".to_string(),
+            ));
+        }
+        let size = vector_size / m;
+        let rows = model.word_count();
+        let matrix = model.matrix();
+
+        let mut rng = Rng::new(self.seed);
+        let mut sample: Vec<usize> = (0..rows).collect();
+        if rows > self.sample_size {
+            for i in 0..self.sample_size {
+                let j = i + rng.below(rows - i);
+                sample.swap(i, j);
+            }
+            sample.truncate(self.sample_size);
+        }
+        let k = self.centroids.min(sample.len());
+
+        let mut codebooks = Vec::with_capacity(m * k * size);
+        let mut points = Vec::with_capacity(sample.len() * size);
+        for j in 0..m {
+            points.clear();
+            for &row in &sample {
+                let start = row * vector_size + j * size;
+                points.extend_from_slice(&matrix[start..start + size]);
+            }
+            codebooks.extend(kmeans(&points, size, k, self.iterations, &mut rng));
+        }
+
+        let mut codes = Vec::with_capacity(rows * m);
+        for row in 0..rows {
+            let vector = &matrix[row * vector_size..(row + 1) * vector_size];
+            for (j, subvector) in vector.chunks_exact(size).enumerate() {
+                let codebook = &codebooks[j * k * size..(j + 1) * k * size];
+                codes.push(nearest_centroid(subvector, codebook, size) as u8);
+            }
+        }
+
+        let words: Vec<String> = model.get_words().cloned().collect();
+        Ok(PqWordVector::from_parts(
+            words,
+            vector_size,
+            m,
+            k,
+            codebooks,
+            None,
+            codes,
+        ))
+    }
+}
+
+/// Word vector space compressed with product quantization
+pub struct PqWordVector {
+    words: Vec<String>,
+    indices: HashMap<String, usize>,
+    vector_size: usize,
+    subquantizers: usize,
+    centroids: usize,
+    codebooks: Vec<f32>,
+    norms: Vec<f32>,
+    codes: Vec<u8>,
+}
+
+impl PqWordVector {
+    /// Assemble a model, computing the norms of the reconstructed vectors unless given.
+    fn from_parts(
+        words: Vec<String>,
+        vector_size: usize,
+        subquantizers: usize,
+        centroids: usize,
+        codebooks: Vec<f32>,
+        norms: Option<Vec<f32>>,
+        codes: Vec<u8>,
+    ) -> PqWordVector {
+        let mut indices = HashMap::with_capacity(words.len());
+        for (index, word) in words.iter().enumerate() {
+            indices.entry(word.clone()).or_insert(index);
+        }
+        let mut model = PqWordVector {
+            words,
+            indices,
+            vector_size,
+            subquantizers,
+            centroids,
+            codebooks,
+            norms: Vec::new(),
+            codes,
+        };
+        model.norms = match norms {
+            Some(norms) => norms,
+            None => (0..model.words.len())
+                .map(|index| {
+                    let vector = model.reconstruct(index);
+                    utils::dot_product(&vector, &vector).sqrt()
+                })
+                .collect(),
+        };
+        model
+    }
+
+    fn subvector_size(&self) -> usize {
+        self.vector_size / self.subquantizers
+    }
+
+    fn centroid(&self, subquantizer: usize, code: u8) -> &[f32] {
+        let size = self.subvector_size();
+        let start = (subquantizer * self.centroids + code as usize) * size;
+        &self.codebooks[start..start + size]
+    }
+
+    /// Concatenate the centroids of the codes of row `index`.
+    fn reconstruct(&self, index: usize) -> Vec<f32> {
+        let codes = &self.codes[index * self.subquantizers..(index + 1) * self.subquantizers];
+        let mut vector = Vec::with_capacity(self.vector_size);
+        for (j, &code) in codes.iter().enumerate() {
+            vector.extend_from_slice(self.centroid(j, code));
+        }
+        vector
+    }
+
+    /// Store the compressed model in a file
+    pub fn save(&self, file_name: &str) -> Result<(), Word2VecError> {
+        let file = File::create(file_name)?;
+        self.write(BufWriter::new(file))
+    }
+
+    /// Write the compressed model to a writer
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Word2VecError> {
+        writer.write_all(MAGIC)?;
+        writer.write_u32::<LittleEndian>(VERSION)?;
+        writer.write_u32::<LittleEndian>(0)?;
+        for &value in &[
+            self.words.len(),
+            self.vector_size,
+            self.subquantizers,
+            self.centroids,
+        ] {
+            writer.write_u64::<LittleEndian>(value as u64)?;
+        }
+        for word in &self.words {
+            let len = u32::try_from(word.len()).map_err(|_| invalid("word is too long"))?;
+            writer.write_u32::<LittleEndian>(len)?;
+            writer.write_all(word.as_bytes())?;
+        }
+        for &x in self.codebooks.iter().chain(&self.norms) {
+            writer.write_f32::<LittleEndian>(x)?;
+        }
+        writer.write_all(&self.codes)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load a model stored by [`save`](#method.save)
+    pub fn load(file_name: &str) -> Result<PqWordVector, Word2VecError> {
+        let file = File::open(file_name)?;
+        PqWordVector::load_from_reader(BufReader::new(file))
+    }
+
+    /// Load a model written by [`write`](#method.write) from a reader
+    pub fn load_from_reader<R: BufRead>(mut reader: R) -> Result<PqWordVector, Word2VecError> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("missing magic bytes"));
+        }
+        if reader.read_u32::<LittleEndian>()? != VERSION {
+            return Err(invalid("unsupported format version"));
+        }
+        reader.read_u32::<LittleEndian>()?;
+        let mut header = [0usize; 4];
+        for value in header.iter_mut() {
+            *value = reader.read_u64::<LittleEndian>()? as usize;
+        }
+        let [vocabulary_size, vector_size, subquantizers, centroids] = header;
+        if vector_size == 0 {
+            return Err(invalid("vectors have no components"));
+        }
+        if subquantizers == 0 || !vector_size.is_multiple_of(subquantizers) {
+            return Err(invalid("subquantizers don't divide the vector size"));
+        }
+        if centroids > MAX_CENTROIDS {
+            return Err(invalid("too many centroids"));
+        }
+
+        let mut words = Vec::new();
+        for _ in 0..vocabulary_size {
+            let len = reader.read_u32::<LittleEndian>()?;
+            let mut bytes = Vec::new();
+            reader
+                .by_ref()
+                .take(u64::from(len))
+                .read_to_end(&mut bytes)?;
+            if bytes.len() != len as usize {
+                return Err(invalid("truncated word"));
+            }
+            words.push(String::from_utf8(bytes).map_err(|_| invalid("word is not valid UTF-8"))?);
+        }
+        let mut read_floats = |count: usize| -> Result<Vec<f32>, Word2VecError> {
+            let mut floats = Vec::new();
+            for _ in 0..count {
+                floats.push(reader.read_f32::<LittleEndian>()?);
+            }
+            Ok(floats)
+        };
+        let codebooks_len = centroids
+            .checked_mul(vector_size)
+            .ok_or_else(|| invalid("too many codebook components"))?;
+        let codebooks = read_floats(codebooks_len)?;
+        let norms = read_floats(vocabulary_size)?;
+        let mut codes = Vec::new();
+        let codes_len = vocabulary_size
+            .checked_mul(subquantizers)
+            .ok_or_else(|| invalid("too many codes"))?;
+        reader.take(codes_len as u64).read_to_end(&mut codes)?;
+        if codes.len() != codes_len {
+            return Err(invalid("truncated codes"));
+        }
+        if codes.iter().any(|&code| code as usize >= centroids) {
+            return Err(invalid("code refers to a missing centroid"));
+        }
+        Ok(PqWordVector::from_parts(
+            words,
+            vector_size,
+            subquantizers,
+            centroids,
+            codebooks,
+            Some(norms),
+            codes,
+        ))
+    }
+
+    /// Get the approximate, normalized word vector for the given word.
+    pub fn get_vector(&self, word: &str) -> Option<Vec<f32>> {
+        self.index_of(word)
+            .map(|index| self.vector(index).into_owned())
+    }
+
+    /// Get the row index of the given word.
+    pub fn index_of(&self, word: &str) -> Option<usize> {
+        self.indices.get(word).cloned()
+    }
+
+    /// Compute the approximate cosine distance to similar words.
+    ///
+    /// See [`WordVector::cosine`](../wordvectors/struct.WordVector.html#method.cosine).
+    pub fn cosine(&self, word: &str, n: usize) -> Option<Vec<(String, f32)>> {
+        search::cosine(self, word, n, self.words.len())
+    }
+
+    /// Find the words closest to the mean of the `pos` vectors and the negated `neg` vectors.
+    ///
+    /// See [`WordVector::analogy`](../wordvectors/struct.WordVector.html#method.analogy).
+    pub fn analogy(
+        &self,
+        pos: Vec<&str>,
+        neg: Vec<&str>,
+        n: usize,
+    ) -> Result<AnalogyResult, Word2VecError> {
+        search::analogy(self, AnalogyMethod::CosAdd, &pos, &neg, n, self.words.len())
+    }
+
+    /// Get the number of all known words from the vocabulary.
+    pub fn word_count(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Return the number of columns of the word vector.
+    pub fn get_col_count(&self) -> usize {
+        self.vector_size
+    }
+
+    /// Return the number of subquantizers, which is the number of bytes per word.
+    pub fn subquantizers(&self) -> usize {
+        self.subquantizers
+    }
+
+    /// Get all known words from the vocabulary, in the order of the source model.
+    pub fn get_words(&self) -> Words<'_> {
+        Words::new(&self.words)
+    }
+}
+
+impl VectorSpace for PqWordVector {
+    fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    fn rows(&self) -> usize {
+        self.words.len()
+    }
+
+    fn vector(&self, index: usize) -> Cow<'_, [f32]> {
+        let mut vector = self.reconstruct(index);
+        let norm = self.norms[index];
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        Cow::Owned(vector)
+    }
+
+    fn dots(&self, query: &[f32], rows: usize) -> Vec<f32> {
+        let m = self.subquantizers;
+        let mut table = Vec::with_capacity(m * self.centroids);
+        for (j, subquery) in query.chunks_exact(self.subvector_size()).enumerate() {
+            for code in 0..self.centroids {
+                let centroid = self.centroid(j, code as u8);
+                table.push(
+                    subquery
+                        .iter()
+                        .zip(centroid)
+                        .map(|(x, y)| x * y)
+                        .sum::<f32>(),
+                );
+            }
+        }
+        utils::map_rows(rows.min(self.words.len()), |index| {
+            let codes = &self.codes[index * m..(index + 1) * m];
+            let dot: f32 = codes
+                .iter()
+                .enumerate()
+                .map(|(j, &code)| table[j * self.centroids + code as usize])
+                .sum();
+            let norm = self.norms[index];
+            if norm > 0.0 {
+                dot / norm
+            } else {
+                0.0
+            }
+        })
+    }
+
+    fn word(&self, index: usize) -> &str {
+        &self.words[index]
+    }
+
+    fn index_of(&self, word: &str) -> Option<usize> {
+        PqWordVector::index_of(self, word)
+    }
+}
@@ -0,0 +1,417 @@
+//! Brute-force similarity search shared by the word vector space implementations
+use std::borrow::Cow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use crate::errors::Word2VecError;
+use crate::utils;
+
+/// Row-oriented view on a vector space with normalized vectors
+pub trait VectorSpace {
+    /// Number of components of each vector.
+    fn vector_size(&self) -> usize;
+
+    /// Number of words and hence of rows.
+    fn rows(&self) -> usize;
+
+    /// The normalized vector of the word in row `index`.
+    fn vector(&self, index: usize) -> Cow<'_, [f32]>;
+
+    /// The dot products of `query` with the normalized vectors in the first `rows` rows
+    ///
+    /// Scoring all rows at once lets a space prepare `query` only once, for example to quantize
+    /// it.
+    fn dots(&self, query: &[f32], rows: usize) -> Vec<f32>;
+
+    /// The word in row `index`.
+    fn word(&self, index: usize) -> &str;
+
+    /// The row of `word`, if it is part of the vocabulary.
+    fn index_of(&self, word: &str) -> Option<usize>;
+}
+
+/// How [`WordVector::analogy_with`](struct.WordVector.html#method.analogy_with) scores the
+/// candidate answers of an analogy
+///
+/// The analogy "a is to b as c is to d" is asked for by `pos = [b, c]` and `neg = [a]`, looking
+/// for d.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum AnalogyMethod {
+    /// 3CosAdd: the cosine similarity of d to the mean of the `pos` vectors and the negated
+    /// `neg` vectors, as computed by the original word2vec tool
+    #[default]
+    CosAdd,
+    /// 3CosMul (Levy and Goldberg, 2014): the product of the similarities of d to the `pos`
+    /// words divided by the product of its similarities to the `neg` words, which keeps a
+    /// single large similarity from dominating
+    CosMul,
+    /// Pair direction: the cosine similarity of d - c to b - a, which only considers the
+    /// direction of the offset; requires exactly two `pos` words and one `neg` word
+    PairDirection,
+}
+
+/// Answer to an analogy query, see [`WordVector::analogy`](struct.WordVector.html#method.analogy)
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalogyResult {
+    /// The words which solve the analogy best, with their scores, best first
+    pub words: Vec<(String, f32)>,
+    /// The given words which are not in the vocabulary and were left out
+    pub unknown: Vec<String>,
+}
+
+/// Keeps 3CosMul from dividing by zero, as proposed by Levy and Goldberg
+const COS_MUL_EPSILON: f32 = 0.001;
+
+/// A row together with its similarity to a query, ordered by similarity and then by row, where
+/// lower rows rank higher
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Candidate {
+    pub similarity: f32,
+    pub index: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        self.similarity
+            .total_cmp(&other.similarity)
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Collects the `n` best candidates pushed to it
+///
+/// Only `n` candidates are kept at any time, in a min-heap whose root is the one to drop next.
+/// Candidates with a NaN or infinite similarity are dropped right away.
+struct TopK {
+    n: usize,
+    heap: BinaryHeap<Reverse<Candidate>>,
+}
+
+impl TopK {
+    fn new(n: usize) -> TopK {
+        TopK {
+            n,
+            heap: BinaryHeap::with_capacity(n),
+        }
+    }
+
+    fn push(&mut self, candidate: Candidate) {
+        if !candidate.similarity.is_finite() {
+            return;
+        }
+        if self.heap.len() < self.n {
+            self.heap.push(Reverse(candidate));
+        } else if let Some(mut worst) = self.heap.peek_mut() {
+            if candidate > worst.0 {
+                *worst = Reverse(candidate);
+            }
+        }
+    }
+
+    /// The collected candidates, best first.
+    fn into_sorted_vec(self) -> Vec<Candidate> {
+        let mut best: Vec<Candidate> = self.heap.into_iter().map(|c| c.0).collect();
+        best.sort_unstable_by(|a, b| b.cmp(a));
+        best
+    }
+}
+
+/// The `n` best of `candidates` with a finite similarity, best first
+pub fn top_k<I: Iterator<Item = Candidate>>(candidates: I, n: usize) -> Vec<Candidate> {
+    let mut best = TopK::new(n);
+    for candidate in candidates {
+        best.push(candidate);
+    }
+    best.into_sorted_vec()
+}
+
+/// The `n` best rows by `scores`, skipping the rows in `exclude`
+///
+/// Words are only copied for the returned rows.
+fn best<S: VectorSpace>(
+    space: &S,
+    scores: Vec<f32>,
+    exclude: &[usize],
+    n: usize,
+) -> Vec<(String, f32)> {
+    let candidates = scores
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !exclude.contains(index))
+        .map(|(index, similarity)| Candidate { similarity, index });
+    top_k(candidates, n)
+        .into_iter()
+        .map(|c| (space.word(c.index).to_string(), c.similarity))
+        .collect()
+}
+
+/// Score the first `restrict_vocab` rows against `query` and return the `n` best ones, skipping
+/// the rows in `exclude`.
+fn nearest<S: VectorSpace>(
+    space: &S,
+    query: &[f32],
+    exclude: &[usize],
+    n: usize,
+    restrict_vocab: usize,
+) -> Vec<(String, f32)> {
+    let scores = space.dots(query, restrict_vocab.min(space.rows()));
+    best(space, scores, exclude, n)
+}
+
+/// The `n` words closest to `word` among the first `restrict_vocab` rows, see
+/// `WordVector::cosine`.
+pub fn cosine<S: VectorSpace>(
+    space: &S,
+    word: &str,
+    n: usize,
+    restrict_vocab: usize,
+) -> Option<Vec<(String, f32)>> {
+    let index = space.index_of(word)?;
+    Some(nearest(
+        space,
+        &space.vector(index),
+        &[index],
+        n,
+        restrict_vocab,
+    ))
+}
+
+/// The `n` words among the first `restrict_vocab` rows closest to `vector`, skipping the words in
+/// `exclude`, see `WordVector::similar_by_vector`.
+pub fn similar_by_vector<S: VectorSpace>(
+    space: &S,
+    vector: &[f32],
+    n: usize,
+    exclude: &[&str],
+    restrict_vocab: usize,
+) -> Result<Vec<(String, f32)>, Word2VecError> {
+    if vector.len() != space.vector_size() {
+        return Err(Word2VecError::WrongVectorSize {
+            expected: space.vector_size(),
+            found: vector.len(),
+        });
+    }
+    if !vector.iter().all(|x| x.is_finite()) {
+        return Err(Word2VecError::InvalidParameter(
+            "query vector has NaN or infinite components".to_string(),
+        ));
+    }
+    let mut query = vector.to_vec();
+    // a zero vector can't be normalized and is equally similar to all words
+    if query.iter().any(|&x| x != 0.0) {
+        utils::vector_norm(&mut query);
+    }
+    let exclude: Vec<usize> = exclude
+        .iter()
+        .filter_map(|word| space.index_of(word))
+        .collect();
+    Ok(nearest(space, &query, &exclude, n, restrict_vocab))
+}
+
+/// The `n` words among the first `restrict_vocab` rows which solve the analogy given by `pos`
+/// and `neg` best according to `method`, see `WordVector::analogy_with`.
+pub fn analogy<S: VectorSpace>(
+    space: &S,
+    method: AnalogyMethod,
+    pos: &[&str],
+    neg: &[&str],
+    n: usize,
+    restrict_vocab: usize,
+) -> Result<AnalogyResult, Word2VecError> {
+    if pos.is_empty() && neg.is_empty() {
+        return Err(Word2VecError::InvalidParameter(
+            "an analogy needs at least one word".to_string(),
+        ));
+    }
+    if method == AnalogyMethod::PairDirection && (pos.len() != 2 || neg.len() != 1) {
+        return Err(Word2VecError::InvalidParameter(
+            "pair direction needs two positive words and one negative word".to_string(),
+        ));
+    }
+    let unknown: Vec<String> = pos
+        .iter()
+        .chain(neg)
+        .filter(|word| space.index_of(word).is_none())
+        .map(|word| word.to_string())
+        .collect();
+    let pos: Vec<usize> = pos.iter().filter_map(|word| space.index_of(word)).collect();
+    let neg: Vec<usize> = neg.iter().filter_map(|word| space.index_of(word)).collect();
+    // unknown words are left out, unless nothing remains or the method needs all of them
+    if (pos.is_empty() && neg.is_empty())
+        || (method == AnalogyMethod::PairDirection && !unknown.is_empty())
+    {
+        return Err(Word2VecError::UnknownWords(unknown));
+    }
+    let exclude: Vec<usize> = pos.iter().chain(&neg).cloned().collect();
+    let rows = restrict_vocab.min(space.rows());
+    let scores = match method {
+        AnalogyMethod::CosAdd => {
+            let mut vectors: Vec<Vec<f32>> = Vec::new();
+            for &index in &pos {
+                vectors.push(space.vector(index).into_owned());
+            }
+            for &index in &neg {
+                vectors.push(space.vector(index).iter().map(|x| -x).collect());
+            }
+            let mut mean: Vec<f32> = Vec::with_capacity(space.vector_size());
+            for i in 0..space.vector_size() {
+                mean.push(utils::mean(vectors.iter().map(|v| v[i])));
+            }
+            space.dots(&mean, rows)
+        }
+        AnalogyMethod::CosMul => {
+            // shift the similarities to [0, 1], so that products and quotients are meaningful
+            let similarities = |index: usize| -> Vec<f32> {
+                let mut scores = space.dots(&space.vector(index), rows);
+                scores.iter_mut().for_each(|x| *x = (*x + 1.0) / 2.0);
+                scores
+            };
+            let mut numerator = vec![1f32; rows];
+            for &index in &pos {
+                for (x, y) in numerator.iter_mut().zip(similarities(index)) {
+                    *x *= y;
+                }
+            }
+            let mut denominator = vec![1f32; rows];
+            for &index in &neg {
+                for (x, y) in denominator.iter_mut().zip(similarities(index)) {
+                    *x *= y;
+                }
+            }
+            numerator
+                .iter()
+                .zip(&denominator)
+                .map(|(x, y)| x / (y + COS_MUL_EPSILON))
+                .collect()
+        }
+        AnalogyMethod::PairDirection => {
+            // a is to b as c is to d, given as pos = [b, c] and neg = [a]
+            let (b, c, a) = (pos[0], pos[1], neg[0]);
+            let direction: Vec<f32> = space
+                .vector(b)
+                .iter()
+                .zip(space.vector(a).iter())
+                .map(|(b, a)| b - a)
+                .collect();
+            let direction_norm = utils::dot_product(&direction, &direction).sqrt();
+            let along_direction = space.dots(&direction, rows);
+            let along_c = space.dots(&space.vector(c), rows);
+            let c_direction = utils::dot_product(&space.vector(c), &direction);
+            // cos(d - c, b - a), where |d - c|^2 = 2 - 2 d.c for unit vectors
+            along_direction
+                .iter()
+                .zip(&along_c)
+                .map(|(d_direction, d_c)| {
+                    let norm = (2.0 - 2.0 * d_c).max(0.0).sqrt() * direction_norm;
+                    if norm > 0.0 {
+                        (d_direction - c_direction) / norm
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        }
+    };
+    Ok(AnalogyResult {
+        words: best(space, scores, &exclude, n),
+        unknown,
+    })
+}
+
+/// Number of queries of a batch which are compared with a row while it is in cache
+const QUERY_BLOCK: usize = 64;
+
+/// The `n` words closest to each of `words` among the first `restrict_vocab` rows, see
+/// `WordVector::cosine_batch`.
+///
+/// The queries are processed in blocks, like a blocked matrix multiplication: each row is
+/// fetched once per block and compared with all of its queries. With the `parallel` feature, the
+/// blocks are distributed over all cores.
+pub fn cosine_batch<S: VectorSpace + Sync>(
+    space: &S,
+    words: &[&str],
+    n: usize,
+    restrict_vocab: usize,
+) -> Vec<Option<Vec<(String, f32)>>> {
+    let rows = restrict_vocab.min(space.rows());
+    let queries: Vec<Option<usize>> = words.iter().map(|word| space.index_of(word)).collect();
+    let known: Vec<usize> = queries.iter().filter_map(|&query| query).collect();
+    let scan = |block: &[usize]| -> Vec<Vec<Candidate>> {
+        let vectors: Vec<Cow<'_, [f32]>> = block.iter().map(|&query| space.vector(query)).collect();
+        let mut best: Vec<TopK> = block.iter().map(|_| TopK::new(n)).collect();
+        for row in 0..rows {
+            let vector = space.vector(row);
+            for ((&query, query_vector), best) in block.iter().zip(&vectors).zip(&mut best) {
+                if row != query {
+                    best.push(Candidate {
+                        similarity: utils::dot_product(&vector, query_vector),
+                        index: row,
+                    });
+                }
+            }
+        }
+        best.into_iter().map(TopK::into_sorted_vec).collect()
+    };
+
+    #[cfg(feature = "parallel")]
+    let found: Vec<Vec<Candidate>> = {
+        use rayon::prelude::*;
+        known.par_chunks(QUERY_BLOCK).flat_map_iter(scan).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let found: Vec<Vec<Candidate>> = known.chunks(QUERY_BLOCK).flat_map(scan).collect();
+
+    let mut found = found.into_iter();
+    queries
+        .iter()
+        .map(|query| {
+            // the results of the known words are in order
+            query.and_then(|_| found.next()).map(|best| {
+                best.into_iter()
+                    .map(|c| (space.word(c.index).to_string(), c.similarity))
+                    .collect()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_k() {
+        let candidates = [0.5, 0.1, 0.9, 0.5, -0.3, 0.7]
+            .iter()
+            .enumerate()
+            .map(|(index, &similarity)| Candidate { similarity, index });
+        let best: Vec<usize> = top_k(candidates.clone(), 4)
+            .iter()
+            .map(|c| c.index)
+            .collect();
+        // ties keep the order of the rows
+        assert_eq!(best, vec![2, 5, 0, 3]);
+        assert_eq!(top_k(candidates.clone(), 10).len(), 6);
+        assert!(top_k(candidates, 0).is_empty());
+
+        let candidates = [f32::NAN, 0.1, f32::INFINITY]
+            .iter()
+            .enumerate()
+            .map(|(index, &similarity)| Candidate { similarity, index });
+        assert_eq!(
+            top_k(candidates, 3),
+            vec![Candidate {
+                similarity: 0.1,
+                index: 1
+            }]
+        );
+    }
+}
@@ -32,8 +32,59 @@ pub fn dot_product(arr1: &[f32], arr2: &[f32]) -> f32 {
     result
 }
 
+/// Dot product of a half-precision vector and a single-precision vector
+#[cfg(feature = "half")]
+pub fn dot_product_f16(arr1: &[half::f16], arr2: &[f32]) -> f32 {
+    let mut result: f32 = 0.0;
+    for (elem1, elem2) in arr1.iter().zip(arr2.iter()) {
+        result += elem1.to_f32() * elem2;
+    }
+    result
+}
+
+/// Dot product of a bfloat16 vector and a single-precision vector
+#[cfg(feature = "half")]
+pub fn dot_product_bf16(arr1: &[half::bf16], arr2: &[f32]) -> f32 {
+    let mut result: f32 = 0.0;
+    for (elem1, elem2) in arr1.iter().zip(arr2.iter()) {
+        result += elem1.to_f32() * elem2;
+    }
+    result
+}
+
+/// Dot product of two vectors of signed 8 bit integers, accumulated exactly in 32 bits
+pub fn dot_product_i8(arr1: &[i8], arr2: &[i8]) -> i32 {
+    let mut result: i32 = 0;
+    for (&elem1, &elem2) in arr1.iter().zip(arr2.iter()) {
+        result += i32::from(elem1) * i32::from(elem2);
+    }
+    result
+}
+
+/// Quantize `vector` to signed 8 bit integers, append them to `output` and return the scale
+/// factor which maps them back
+///
+/// The component with the largest magnitude is mapped to 127 or -127.
+pub fn quantize(vector: &[f32], output: &mut Vec<i8>) -> f32 {
+    let scale = vector.iter().fold(0f32, |max, x| max.max(x.abs())) / 127.0;
+    output.extend(vector.iter().map(|&x| quantize_component(x, scale)));
+    scale
+}
+
+/// Round `x / scale` to the nearest signed 8 bit integer, saturating at 127 and -127
+pub fn quantize_component(x: f32, scale: f32) -> i8 {
+    if scale > 0.0 {
+        (x / scale).round().clamp(-127.0, 127.0) as i8
+    } else {
+        0
+    }
+}
+
+/// Normalize `vector` to unit length and return its original length
+///
+/// A vector of length zero is left unchanged rather than divided by zero.
 #[cfg(feature = "simd")]
-pub fn vector_norm(vector: &mut Vec<f32>) {
+pub fn vector_norm(vector: &mut [f32]) -> f32 {
     use std::ops::Add;
     use std::ops::Mul;
     assert_eq!(vector.len() % 4, 0);
@@ -52,19 +103,45 @@ pub fn vector_norm(vector: &mut Vec<f32>) {
         .map(|(l, r)| f32x4::mul(*l, r))
         .fold(sums, f32x4::add);
 
-    let sum = 1.0 / sums.reduce_sum().sqrt();
+    let norm = sums.reduce_sum().sqrt();
+    if norm == 0.0 {
+        return norm;
+    }
+    let sum = 1.0 / norm;
 
     for x in vector.iter_mut() {
         (*x) *= sum;
     }
+    norm
 }
 
+/// Normalize `vector` to unit length and return its original length
+///
+/// A vector of length zero is left unchanged rather than divided by zero.
 #[cfg(not(feature = "simd"))]
-pub fn vector_norm(vector: &mut [f32]) {
-    let sum = 1.0 / vector.iter().fold(0f32, |sum, &x| sum + (x * x)).sqrt();
+pub fn vector_norm(vector: &mut [f32]) -> f32 {
+    let norm = vector.iter().fold(0f32, |sum, &x| sum + (x * x)).sqrt();
+    if norm == 0.0 {
+        return norm;
+    }
+    let sum = 1.0 / norm;
     for x in vector.iter_mut() {
         (*x) *= sum;
     }
+    norm
+}
+
+/// Compute `score` for each row in `0..rows`, on all cores with the `parallel` feature
+#[cfg(feature = "parallel")]
+pub fn map_rows<F: Fn(usize) -> f32 + Sync + Send>(rows: usize, score: F) -> Vec<f32> {
+    use rayon::prelude::*;
+    (0..rows).into_par_iter().map(score).collect()
+}
+
+/// Compute `score` for each row in `0..rows`, on all cores with the `parallel` feature
+#[cfg(not(feature = "parallel"))]
+pub fn map_rows<F: Fn(usize) -> f32>(rows: usize, score: F) -> Vec<f32> {
+    (0..rows).map(score).collect()
 }
 
 /// Get the mean (average) of the given Iterator of numbers
@@ -73,6 +150,32 @@ pub fn mean<Iterable: Iterator<Item = f32>>(numbers: Iterable) -> f32 {
     sum / (count as f32)
 }
 
+/// Small deterministic pseudo-random number generator (SplitMix64)
+///
+/// Reproducible sampling only needs a fast, seedable source of bits, not a cryptographic one.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`, which must not be empty
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,6 +188,18 @@ mod tests {
         assert!((-54.124058 - dot_product(&x, &y)).abs() < 0.000005);
     }
 
+    #[test]
+    fn test_quantize() {
+        let x = vec![0.5, -0.25, 0.125, 0.0];
+        let mut quantized = Vec::new();
+        let scale = quantize(&x, &mut quantized);
+        assert_eq!(quantized, vec![127, -64, 32, 0]);
+        assert_eq!(dot_product_i8(&quantized, &quantized), 21249);
+        for (&q, &x) in quantized.iter().zip(x.iter()) {
+            assert!((f32::from(q) * scale - x).abs() <= scale / 2.0);
+        }
+    }
+
     #[bench]
     fn bench_dot_product(b: &mut Bencher) {
         let x = test::black_box(vec![
@@ -1,12 +1,355 @@
-use std::io::BufRead;
+use std::io::{self, BufRead, Chain, Cursor, Read};
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
 
 use errors::Word2VecError;
 
+/// Maximum number of bytes inspected by [`detect_format`](fn.detect_format.html) to find the
+/// header and the end of the first text record.
+const SNIFF_LIMIT: u64 = 1 << 20;
+
+/// Largest vector size accepted in a header
+///
+/// The buffers of a record are allocated from the header, so a corrupt header must not be able
+/// to request an arbitrary amount of memory.
+const MAX_VECTOR_SIZE: usize = 1 << 20;
+
+/// On-disk formats of word vector files
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VectorFormat {
+    /// Binary word2vec format, read by [`WordVectorReader`](struct.WordVectorReader.html)
+    Binary,
+    /// Text word2vec format with a header line, also used by fastText's `.vec` files; read by
+    /// [`TextWordVectorReader`](struct.TextWordVectorReader.html)
+    Text,
+    /// Headerless text format used by GloVe, read by [`GloveReader`](struct.GloveReader.html)
+    Glove,
+}
+
+/// Reader returned by [`detect_format`](fn.detect_format.html), replaying the inspected bytes
+/// before the rest of the input.
+pub type SniffedReader<R> = Chain<Cursor<Vec<u8>>, R>;
+
+/// Handling of words which are not valid UTF-8
+///
+/// Some models, e.g. the GoogleNews vectors, contain words which were cut off in the middle of a
+/// multi-byte character.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Utf8Policy {
+    /// Fail with `Word2VecError::InvalidUtf8Word`
+    #[default]
+    Strict,
+    /// Replace invalid sequences with `U+FFFD REPLACEMENT CHARACTER`
+    Lossy,
+    /// Skip the record
+    Skip,
+    /// Replace invalid sequences like `Lossy`, but also keep the original bytes, see
+    /// [`RecordReader::raw_word`](trait.RecordReader.html#tymethod.raw_word)
+    Raw,
+}
+
+/// Type of the vector components in binary files
+///
+/// The vectors are converted to `f32` when they are read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ElementType {
+    /// Single precision, as written by word2vec
+    #[default]
+    F32,
+    /// Double precision
+    F64,
+    /// IEEE 754 half precision; requires the `half` feature
+    #[cfg(feature = "half")]
+    F16,
+    /// bfloat16; requires the `half` feature
+    #[cfg(feature = "half")]
+    Bf16,
+}
+
+impl ElementType {
+    /// Number of bytes of each component.
+    pub fn size(self) -> usize {
+        match self {
+            ElementType::F32 => 4,
+            ElementType::F64 => 8,
+            #[cfg(feature = "half")]
+            ElementType::F16 | ElementType::Bf16 => 2,
+        }
+    }
+}
+
+/// Byte order of the vector components in binary files
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Endianness {
+    /// Least significant byte first, as written by word2vec on common platforms
+    #[default]
+    Little,
+    /// Most significant byte first
+    Big,
+}
+
+/// Read `vector.len()` binary components of the given layout into `vector`, using `buffer` for
+/// components which are not `f32`.
+fn read_components<R: Read>(
+    reader: &mut R,
+    vector: &mut [f32],
+    buffer: &mut Vec<u8>,
+    element_type: ElementType,
+    endianness: Endianness,
+) -> io::Result<()> {
+    let little = endianness == Endianness::Little;
+    match element_type {
+        ElementType::F32 if little => return reader.read_f32_into::<LittleEndian>(vector),
+        ElementType::F32 => return reader.read_f32_into::<BigEndian>(vector),
+        _ => {}
+    }
+
+    let size = element_type.size();
+    buffer.resize(size * vector.len(), 0);
+    reader.read_exact(buffer)?;
+    for (x, bytes) in vector.iter_mut().zip(buffer.chunks_exact(size)) {
+        *x = match element_type {
+            ElementType::F64 if little => LittleEndian::read_f64(bytes) as f32,
+            ElementType::F64 => BigEndian::read_f64(bytes) as f32,
+            #[cfg(feature = "half")]
+            ElementType::F16 if little => {
+                half::f16::from_bits(LittleEndian::read_u16(bytes)).to_f32()
+            }
+            #[cfg(feature = "half")]
+            ElementType::F16 => half::f16::from_bits(BigEndian::read_u16(bytes)).to_f32(),
+            #[cfg(feature = "half")]
+            ElementType::Bf16 if little => {
+                half::bf16::from_bits(LittleEndian::read_u16(bytes)).to_f32()
+            }
+            #[cfg(feature = "half")]
+            ElementType::Bf16 => half::bf16::from_bits(BigEndian::read_u16(bytes)).to_f32(),
+            ElementType::F32 => unreachable!(),
+        };
+    }
+    Ok(())
+}
+
+/// Outcome of decoding a word or a text line according to a `Utf8Policy`
+enum Decoded {
+    /// Valid or lossily decoded text
+    Text(String),
+    /// Lossily decoded text together with the original bytes
+    Raw(String, Vec<u8>),
+    /// The record is to be skipped
+    Skip,
+}
+
+/// Decode the bytes of a word, or of a text line containing it, of record `record`.
+fn decode(
+    bytes: Vec<u8>,
+    policy: Utf8Policy,
+    record: usize,
+    offset: u64,
+) -> Result<Decoded, Word2VecError> {
+    let bytes = match String::from_utf8(bytes) {
+        Ok(text) => return Ok(Decoded::Text(text)),
+        Err(err) => err.into_bytes(),
+    };
+    match policy {
+        Utf8Policy::Strict => Err(Word2VecError::InvalidUtf8Word { record, offset }),
+        Utf8Policy::Lossy => Ok(Decoded::Text(String::from_utf8_lossy(&bytes).into_owned())),
+        Utf8Policy::Skip => Ok(Decoded::Skip),
+        Utf8Policy::Raw => Ok(Decoded::Raw(
+            String::from_utf8_lossy(&bytes).into_owned(),
+            bytes,
+        )),
+    }
+}
+
+/// Parse the numbers of a header line, if the line consists of exactly two integers.
+fn parse_header_line(line: &[u8]) -> Option<(usize, usize)> {
+    let line = std::str::from_utf8(line).ok()?;
+    let mut fields = line.split_whitespace();
+    let vocabulary_size = fields.next()?.parse::<usize>().ok()?;
+    let vector_size = fields.next()?.parse::<usize>().ok()?;
+    match fields.next() {
+        None => Some((vocabulary_size, vector_size)),
+        Some(_) => None,
+    }
+}
+
+/// Count the vector components of a text record, if the line is a word followed by numbers.
+fn count_text_components(line: &[u8]) -> Option<usize> {
+    let line = std::str::from_utf8(line).ok()?;
+    let mut fields = line.split_whitespace();
+    fields.next()?;
+    let mut count = 0;
+    for field in fields {
+        field.parse::<f32>().ok()?;
+        count += 1;
+    }
+    Some(count)
+}
+
+/// Detect the format of a word vector file by inspecting its header and first record
+///
+/// The inspected bytes are consumed from `reader`; the returned reader yields the complete input
+/// again, so it can be passed on to the reader for the detected format.
+pub fn detect_format<R: BufRead>(
+    mut reader: R,
+) -> Result<(VectorFormat, SniffedReader<R>), Word2VecError> {
+    let mut prefix = Vec::with_capacity(4096);
+    (&mut reader)
+        .take(SNIFF_LIMIT)
+        .read_until(b'\n', &mut prefix)?;
+    if prefix.last() != Some(&b'\n') {
+        return Err(Word2VecError::UnknownFormat(
+            "no header line found at the start of the file".into(),
+        ));
+    }
+    let (_, vector_size) = match parse_header_line(&prefix) {
+        Some(header) => header,
+        None => match count_text_components(&prefix) {
+            Some(count) if count > 0 => {
+                return Ok((VectorFormat::Glove, Cursor::new(prefix).chain(reader)))
+            }
+            _ => {
+                return Err(Word2VecError::UnknownFormat(
+                    "first line is neither a `<vocabulary size> <vector size>` header nor a \
+                     text record"
+                        .into(),
+                ))
+            }
+        },
+    };
+
+    let record_start = prefix.len();
+    (&mut reader)
+        .take(SNIFF_LIMIT)
+        .read_until(b'\n', &mut prefix)?;
+    // a model without records is read the same way by both readers
+    if prefix[record_start..].iter().all(u8::is_ascii_whitespace) && reader.fill_buf()?.is_empty() {
+        return Ok((VectorFormat::Text, Cursor::new(prefix).chain(reader)));
+    }
+    if let Some(count) = count_text_components(&prefix[record_start..]) {
+        if count == vector_size {
+            return Ok((VectorFormat::Text, Cursor::new(prefix).chain(reader)));
+        }
+        if prefix.last() == Some(&b'\n') {
+            return Err(Word2VecError::UnknownFormat(format!(
+                "first text record has {} components, but the header declares {}",
+                count, vector_size
+            )));
+        }
+    }
+
+    // a binary record is a word terminated by a space, followed by the raw vector
+    let word_start = record_start
+        + prefix[record_start..]
+            .iter()
+            .take_while(|b| b.is_ascii_whitespace())
+            .count();
+    let record_end = match prefix[word_start..].iter().position(|&b| b == b' ') {
+        Some(word_len) if word_len > 0 => vector_size
+            .checked_mul(4)
+            .and_then(|size| size.checked_add(word_start + word_len + 1)),
+        _ => {
+            return Err(Word2VecError::UnknownFormat(
+                "first record is neither a text line nor a binary vector".into(),
+            ))
+        }
+    };
+    let record_end = match record_end {
+        Some(record_end) => record_end,
+        None => {
+            return Err(Word2VecError::UnknownFormat(format!(
+                "the header declares too many components ({}) for a binary vector",
+                vector_size
+            )))
+        }
+    };
+    if prefix.len() < record_end {
+        let missing = (record_end - prefix.len()) as u64;
+        if (&mut reader).take(missing).read_to_end(&mut prefix)? < missing as usize {
+            return Err(Word2VecError::UnknownFormat(
+                "first record is neither a text line nor a complete binary vector".into(),
+            ));
+        }
+    }
+    Ok((VectorFormat::Binary, Cursor::new(prefix).chain(reader)))
+}
+
+/// Read the `<vocabulary size> <vector size>` header line which starts both the binary and the
+/// text format, returning both sizes and the length of the line in bytes.
+fn read_header<R: BufRead>(reader: &mut R) -> Result<(usize, usize, u64), Word2VecError> {
+    // Read UTF8 header string from start of file
+    let mut header = String::with_capacity(128);
+    let len = reader.read_line(&mut header)?;
+
+    //Parse 2 integers, separated by whitespace
+    let header_info = header
+        .split_whitespace()
+        .filter_map(|x| x.parse::<usize>().ok())
+        .take(2)
+        .collect::<Vec<usize>>();
+    match header_info.len() {
+        2 if header_info[1] <= MAX_VECTOR_SIZE => Ok((header_info[0], header_info[1], len as u64)),
+        _ => Err(Word2VecError::WrongHeader),
+    }
+}
+
+/// Record-wise access to the word vectors of a file
+///
+/// Each record is read in two steps: first its word, then either its vector is read or skipped.
+/// This allows dropping unwanted records without decoding or allocating their vectors.
+pub trait RecordReader {
+    /// Number of components of each vector.
+    fn vector_size(&self) -> usize;
+
+    /// Read the word of the next record, or `None` at the end of the input.
+    fn read_word(&mut self) -> Result<Option<String>, Word2VecError>;
+
+    /// Read the vector of the current record into `vector`, which has `vector_size` elements.
+    fn read_vector(&mut self, vector: &mut [f32]) -> Result<(), Word2VecError>;
+
+    /// Skip the vector of the current record.
+    fn skip_vector(&mut self) -> Result<(), Word2VecError>;
+
+    /// The original bytes of the current word, if it is not valid UTF-8 and the reader keeps
+    /// them due to `Utf8Policy::Raw`.
+    fn raw_word(&self) -> Option<&[u8]>;
+}
+
+/// Result of reading one `(word, vector)` record
+pub type Record = Result<(String, Vec<f32>), Word2VecError>;
+
+/// Read a complete `(word, vector)` record.
+fn next_record<T: RecordReader>(reader: &mut T) -> Option<Record> {
+    let word = match reader.read_word() {
+        Ok(Some(word)) => word,
+        Ok(None) => return None,
+        Err(err) => return Some(Err(err)),
+    };
+    let mut vector = vec![0f32; reader.vector_size()];
+    Some(reader.read_vector(&mut vector).map(|()| (word, vector)))
+}
+
+/// Reader for the binary word2vec format
+///
+/// Exactly as many records as declared in the header are read. If the input ends early, the
+/// reader fails with `Word2VecError::VocabularySizeMismatch` or, within a record,
+/// `Word2VecError::TruncatedVector`. As an iterator it yields the error and then stops.
+///
+/// By default the components are little-endian `f32` values. Files with other layouts can be
+/// read by setting [`element_type`](#method.element_type) and [`endianness`](#method.endianness).
 pub struct WordVectorReader<R: BufRead> {
     vocabulary_size: usize,
     vector_size: usize,
+    element_type: ElementType,
+    endianness: Endianness,
+    buffer: Vec<u8>,
+    records_read: usize,
+    record_offset: u64,
+    offset: u64,
+    strict: bool,
+    utf8_policy: Utf8Policy,
+    raw_word: Option<Vec<u8>>,
+    failed: bool,
     reader: R,
 }
 
@@ -20,41 +363,567 @@ impl<R: BufRead> WordVectorReader<R> {
     }
 
     pub fn new_from_reader(mut reader: R) -> Result<WordVectorReader<R>, Word2VecError> {
-        // Read UTF8 header string from start of file
-        let mut header = String::with_capacity(128);
-        reader.read_line(&mut header)?;
+        let (vocabulary_size, vector_size, offset) = read_header(&mut reader)?;
+        Ok(WordVectorReader {
+            vocabulary_size,
+            vector_size,
+            element_type: ElementType::F32,
+            endianness: Endianness::Little,
+            buffer: Vec::new(),
+            records_read: 0,
+            record_offset: offset,
+            offset,
+            strict: false,
+            utf8_policy: Utf8Policy::Strict,
+            raw_word: None,
+            failed: false,
+            reader,
+        })
+    }
+
+    /// Validate the file while reading
+    ///
+    /// In strict mode, vectors with NaN or infinite components are rejected with
+    /// `Word2VecError::NonFiniteComponent`. After the records declared in the header, only
+    /// whitespace may follow, or the reader fails with `Word2VecError::TrailingData`.
+    pub fn strict(mut self, strict: bool) -> WordVectorReader<R> {
+        self.strict = strict;
+        self
+    }
+
+    /// Set how words which are not valid UTF-8 are handled, see
+    /// [`Utf8Policy`](enum.Utf8Policy.html).
+    pub fn utf8_policy(mut self, policy: Utf8Policy) -> WordVectorReader<R> {
+        self.utf8_policy = policy;
+        self
+    }
+
+    /// Set the type of the vector components.
+    pub fn element_type(mut self, element_type: ElementType) -> WordVectorReader<R> {
+        self.element_type = element_type;
+        self
+    }
+
+    /// Set the byte order of the vector components.
+    pub fn endianness(mut self, endianness: Endianness) -> WordVectorReader<R> {
+        self.endianness = endianness;
+        self
+    }
+
+    fn truncated(&self) -> Word2VecError {
+        Word2VecError::TruncatedVector {
+            record: self.records_read - 1,
+            offset: self.record_offset,
+        }
+    }
+}
+
+impl<R: BufRead> RecordReader for WordVectorReader<R> {
+    fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    fn read_word(&mut self) -> Result<Option<String>, Word2VecError> {
+        loop {
+            if self.records_read == self.vocabulary_size {
+                if self.strict {
+                    check_trailing(&mut self.reader, &mut self.offset)?;
+                }
+                return Ok(None);
+            }
+            let mut buf = Vec::with_capacity(32);
+            let start = self.offset;
+            self.offset += self.reader.read_until(b' ', &mut buf)? as u64;
+            // the record starts after the line break which ends the previous vector
+            self.record_offset =
+                start + buf.iter().take_while(|b| b.is_ascii_whitespace()).count() as u64;
+            if buf.last() != Some(&b' ') {
+                if buf.iter().all(u8::is_ascii_whitespace) {
+                    return Err(Word2VecError::VocabularySizeMismatch {
+                        expected: self.vocabulary_size,
+                        found: self.records_read,
+                    });
+                }
+                // the input ends within the word
+                self.records_read += 1;
+                return Err(self.truncated());
+            }
+
+            self.records_read += 1;
+            self.raw_word = None;
+            match decode(
+                buf,
+                self.utf8_policy,
+                self.records_read - 1,
+                self.record_offset,
+            )? {
+                Decoded::Text(word) => return Ok(Some(word.trim().into())),
+                Decoded::Raw(word, bytes) => {
+                    self.raw_word = Some(bytes.trim_ascii().to_vec());
+                    return Ok(Some(word.trim_ascii().into()));
+                }
+                Decoded::Skip => self.skip_vector()?,
+            }
+        }
+    }
+
+    fn read_vector(&mut self, vector: &mut [f32]) -> Result<(), Word2VecError> {
+        match read_components(
+            &mut self.reader,
+            vector,
+            &mut self.buffer,
+            self.element_type,
+            self.endianness,
+        ) {
+            Ok(()) => {
+                self.offset += (self.element_type.size() * vector.len()) as u64;
+                if self.strict {
+                    check_finite(vector, self.records_read - 1, self.record_offset)?;
+                }
+                Ok(())
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => Err(self.truncated()),
+            Err(err) => Err(err.into()),
+        }
+    }
 
-        //Parse 2 integers, separated by whitespace
-        let header_info = header
-            .split_whitespace()
-            .filter_map(|x| x.parse::<usize>().ok())
-            .take(2)
-            .collect::<Vec<usize>>();
-        match header_info.len() {
-            2 => Ok(WordVectorReader {
-                vocabulary_size: header_info[0],
-                vector_size: header_info[1],
-                reader,
-            }),
-            _ => Err(Word2VecError::WrongHeader),
+    fn skip_vector(&mut self) -> Result<(), Word2VecError> {
+        let size = (self.element_type.size() * self.vector_size) as u64;
+        let skipped = io::copy(&mut (&mut self.reader).take(size), &mut io::sink())?;
+        self.offset += skipped;
+        if skipped == size {
+            Ok(())
+        } else {
+            Err(self.truncated())
         }
     }
+
+    fn raw_word(&self) -> Option<&[u8]> {
+        self.raw_word.as_deref()
+    }
 }
 
 impl<R: BufRead> Iterator for WordVectorReader<R> {
-    type Item = (String, Vec<f32>);
+    type Item = Record;
 
-    fn next(&mut self) -> Option<(String, Vec<f32>)> {
-        let mut buf = Vec::with_capacity(32);
-        self.reader.read_until(b' ', &mut buf).ok()?;
+    fn next(&mut self) -> Option<Record> {
+        if self.failed {
+            return None;
+        }
+        let record = next_record(self);
+        self.failed = matches!(record, Some(Err(_)));
+        record
+    }
+}
 
-        let word = String::from_utf8(buf).ok()?.trim().into();
-        let mut vector: Vec<f32> = Vec::with_capacity(self.vector_size);
+/// Reader for the plain-text word2vec format
+///
+/// The text format starts with the same header as the binary format, followed by one line per
+/// word: the word itself and the components of its vector, all separated by whitespace. Like
+/// [`WordVectorReader`](struct.WordVectorReader.html), it reads exactly as many records as
+/// declared in the header.
+pub struct TextWordVectorReader<R: BufRead> {
+    vocabulary_size: usize,
+    vector_size: usize,
+    records_read: usize,
+    record_offset: u64,
+    offset: u64,
+    components: String,
+    strict: bool,
+    utf8_policy: Utf8Policy,
+    raw_word: Option<Vec<u8>>,
+    failed: bool,
+    reader: R,
+}
 
+impl<R: BufRead> TextWordVectorReader<R> {
+    pub fn vocabulary_size(&self) -> usize {
+        self.vocabulary_size
+    }
+
+    pub fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    pub fn new_from_reader(mut reader: R) -> Result<TextWordVectorReader<R>, Word2VecError> {
+        let (vocabulary_size, vector_size, offset) = read_header(&mut reader)?;
+        Ok(TextWordVectorReader {
+            vocabulary_size,
+            vector_size,
+            records_read: 0,
+            record_offset: offset,
+            offset,
+            components: String::new(),
+            strict: false,
+            utf8_policy: Utf8Policy::Strict,
+            raw_word: None,
+            failed: false,
+            reader,
+        })
+    }
+
+    /// Validate the file while reading
+    ///
+    /// In strict mode, vectors with NaN or infinite components are rejected with
+    /// `Word2VecError::NonFiniteComponent`. Records with more components than declared in the
+    /// header fail with `Word2VecError::DimensionMismatch` and, after the declared records, only
+    /// whitespace may follow.
+    pub fn strict(mut self, strict: bool) -> TextWordVectorReader<R> {
+        self.strict = strict;
+        self
+    }
+
+    /// Set how words which are not valid UTF-8 are handled, see
+    /// [`Utf8Policy`](enum.Utf8Policy.html).
+    pub fn utf8_policy(mut self, policy: Utf8Policy) -> TextWordVectorReader<R> {
+        self.utf8_policy = policy;
+        self
+    }
+}
+
+impl<R: BufRead> RecordReader for TextWordVectorReader<R> {
+    fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    fn read_word(&mut self) -> Result<Option<String>, Word2VecError> {
+        loop {
+            if self.records_read == self.vocabulary_size {
+                if self.strict {
+                    check_trailing(&mut self.reader, &mut self.offset)?;
+                }
+                return Ok(None);
+            }
+            let (offset, line) =
+                match read_text_line(&mut self.reader, self.vector_size, &mut self.offset)? {
+                    Some(line) => line,
+                    None => {
+                        return Err(Word2VecError::VocabularySizeMismatch {
+                            expected: self.vocabulary_size,
+                            found: self.records_read,
+                        })
+                    }
+                };
+            self.record_offset = offset;
+            self.records_read += 1;
+            let (mut line, bytes) =
+                match decode(line, self.utf8_policy, self.records_read - 1, offset)? {
+                    Decoded::Text(line) => (line, None),
+                    Decoded::Raw(line, bytes) => (line, Some(bytes)),
+                    Decoded::Skip => continue,
+                };
+
+            let start = line.len() - line.trim_start().len();
+            let end = line[start..]
+                .find(char::is_whitespace)
+                .map_or(line.len(), |pos| start + pos);
+
+            self.raw_word = bytes.map(|bytes| raw_span(&bytes, start, end));
+            self.components = line.split_off(end);
+            return Ok(Some(line.split_off(start)));
+        }
+    }
+
+    fn read_vector(&mut self, vector: &mut [f32]) -> Result<(), Word2VecError> {
+        parse_components(
+            &self.components,
+            vector,
+            self.records_read - 1,
+            self.record_offset,
+            self.strict,
+        )
+    }
+
+    fn skip_vector(&mut self) -> Result<(), Word2VecError> {
+        Ok(())
+    }
+
+    fn raw_word(&self) -> Option<&[u8]> {
+        self.raw_word.as_deref()
+    }
+}
+
+impl<R: BufRead> Iterator for TextWordVectorReader<R> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        if self.failed {
+            return None;
+        }
+        let record = next_record(self);
+        self.failed = matches!(record, Some(Err(_)));
+        record
+    }
+}
+
+/// Parse the whitespace-separated vector components of text record `record` into `vector`.
+///
+/// In `strict` mode, surplus and non-finite components are rejected.
+fn parse_components(
+    components: &str,
+    vector: &mut [f32],
+    record: usize,
+    offset: u64,
+    strict: bool,
+) -> Result<(), Word2VecError> {
+    let mut fields = components.split_whitespace();
+    for x in vector.iter_mut() {
+        let field = fields
+            .next()
+            .ok_or(Word2VecError::TruncatedVector { record, offset })?;
+        *x = field
+            .parse::<f32>()
+            .map_err(|_| Word2VecError::InvalidComponent {
+                record,
+                offset,
+                value: field.to_string(),
+            })?;
+    }
+    if strict {
+        let surplus = fields.count();
+        if surplus > 0 {
+            return Err(Word2VecError::DimensionMismatch {
+                record,
+                offset,
+                expected: vector.len(),
+                found: vector.len() + surplus,
+            });
+        }
+        check_finite(vector, record, offset)?;
+    }
+    Ok(())
+}
+
+/// Reject NaN and infinite components in the vector of record `record`.
+fn check_finite(vector: &[f32], record: usize, offset: u64) -> Result<(), Word2VecError> {
+    if vector.iter().all(|x| x.is_finite()) {
+        Ok(())
+    } else {
+        Err(Word2VecError::NonFiniteComponent { record, offset })
+    }
+}
+
+/// Check that only whitespace remains in `reader`, which is at byte `offset` of the input.
+fn check_trailing<R: BufRead>(reader: &mut R, offset: &mut u64) -> Result<(), Word2VecError> {
+    loop {
+        let (consumed, garbage) = {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(());
+            }
+            let whitespace = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+            (whitespace, whitespace < buf.len())
+        };
+        reader.consume(consumed);
+        *offset += consumed as u64;
+        if garbage {
+            return Err(Word2VecError::TrailingData { offset: *offset });
+        }
+    }
+}
+
+/// Read the next non-blank line of a text format file, advancing `offset` past it.
+///
+/// Returns the offset at which the line starts together with its bytes.
+fn read_text_line<R: BufRead>(
+    reader: &mut R,
+    vector_size: usize,
+    offset: &mut u64,
+) -> Result<Option<(u64, Vec<u8>)>, Word2VecError> {
+    let mut buf = Vec::with_capacity(vector_size.min(MAX_VECTOR_SIZE).saturating_mul(12) + 32);
+    loop {
+        buf.clear();
+        let start = *offset;
+        let len = reader.read_until(b'\n', &mut buf)?;
+        if len == 0 {
+            return Ok(None);
+        }
+        *offset += len as u64;
+        // tolerate blank lines, e.g. a trailing empty line at the end of the file
+        if !buf.iter().all(u8::is_ascii_whitespace) {
+            return Ok(Some((start, buf)));
+        }
+    }
+}
+
+/// Cut the original bytes of the word spanning `start..end` of the lossily decoded `bytes`.
+fn raw_span(bytes: &[u8], start: usize, end: usize) -> Vec<u8> {
+    bytes[raw_offset(bytes, start)..raw_offset(bytes, end)].to_vec()
+}
+
+/// Translate a position in the lossily decoded `bytes` into a position in `bytes`.
+///
+/// Lossy decoding keeps valid sequences as they are and replaces each invalid sequence by a
+/// single replacement character; `pos` must not point into such a character.
+fn raw_offset(bytes: &[u8], pos: usize) -> usize {
+    let (mut decoded, mut raw) = (0, 0);
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid().len();
+        if pos <= decoded + valid {
+            return raw + pos - decoded;
+        }
+        decoded += valid;
+        raw += valid;
+        if !chunk.invalid().is_empty() {
+            decoded += char::REPLACEMENT_CHARACTER.len_utf8();
+            raw += chunk.invalid().len();
+        }
+    }
+    raw
+}
+
+/// Reader for the headerless text format used by GloVe
+///
+/// GloVe files contain one word per line followed by its vector components, just like the text
+/// word2vec format, but lack the header line. The vector size is inferred from the first line
+/// and the vocabulary is counted while reading. Since a few GloVe releases contain words with
+/// spaces, all fields in front of the vector components are taken as the word.
+pub struct GloveReader<R: BufRead> {
+    vector_size: usize,
+    words_read: usize,
+    first_line: Option<(u64, Vec<u8>)>,
+    record_offset: u64,
+    offset: u64,
+    components: String,
+    strict: bool,
+    utf8_policy: Utf8Policy,
+    raw_word: Option<Vec<u8>>,
+    failed: bool,
+    reader: R,
+}
+
+impl<R: BufRead> GloveReader<R> {
+    pub fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    /// Number of words read so far; equals the vocabulary size once the reader is exhausted.
+    pub fn words_read(&self) -> usize {
+        self.words_read
+    }
+
+    pub fn new_from_reader(mut reader: R) -> Result<GloveReader<R>, Word2VecError> {
+        let mut offset = 0;
+        let (first_offset, first_line) = match read_text_line(&mut reader, 0, &mut offset)? {
+            Some(line) => line,
+            None => return Err(Word2VecError::WrongHeader),
+        };
+        // the word may not be valid UTF-8, which is handled when it is read
+        let vector_size =
+            match count_text_components(String::from_utf8_lossy(&first_line).as_bytes()) {
+                Some(count) if count > 0 => count,
+                _ => return Err(Word2VecError::WrongHeader),
+            };
+
+        Ok(GloveReader {
+            vector_size,
+            words_read: 0,
+            first_line: Some((first_offset, first_line)),
+            record_offset: first_offset,
+            offset,
+            components: String::new(),
+            strict: false,
+            utf8_policy: Utf8Policy::Strict,
+            raw_word: None,
+            failed: false,
+            reader,
+        })
+    }
+
+    /// Validate the file while reading
+    ///
+    /// In strict mode, vectors with NaN or infinite components are rejected with
+    /// `Word2VecError::NonFiniteComponent`.
+    pub fn strict(mut self, strict: bool) -> GloveReader<R> {
+        self.strict = strict;
+        self
+    }
+
+    /// Set how words which are not valid UTF-8 are handled, see
+    /// [`Utf8Policy`](enum.Utf8Policy.html).
+    pub fn utf8_policy(mut self, policy: Utf8Policy) -> GloveReader<R> {
+        self.utf8_policy = policy;
+        self
+    }
+}
+
+impl<R: BufRead> RecordReader for GloveReader<R> {
+    fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    fn read_word(&mut self) -> Result<Option<String>, Word2VecError> {
+        let (offset, mut line, bytes) = loop {
+            let (offset, line) = match self.first_line.take() {
+                Some(line) => line,
+                None => {
+                    match read_text_line(&mut self.reader, self.vector_size, &mut self.offset)? {
+                        Some(line) => line,
+                        None => return Ok(None),
+                    }
+                }
+            };
+            match decode(line, self.utf8_policy, self.words_read, offset)? {
+                Decoded::Text(line) => break (offset, line, None),
+                Decoded::Raw(line, bytes) => break (offset, line, Some(bytes)),
+                Decoded::Skip => continue,
+            }
+        };
+        self.record_offset = offset;
+        let truncated = Word2VecError::TruncatedVector {
+            record: self.words_read,
+            offset,
+        };
+
+        // the vector components are the last `vector_size` fields, the word is everything before
+        let mut word = line.trim_end();
         for _ in 0..self.vector_size {
-            vector.push(self.reader.read_f32::<LittleEndian>().ok()?);
+            let pos = match word.rfind(char::is_whitespace) {
+                Some(pos) => pos,
+                None => return Err(truncated),
+            };
+            word = word[..pos].trim_end();
+        }
+        let end = word.len();
+        let start = end - word.trim_start().len();
+        if start == end {
+            return Err(truncated);
         }
 
-        Some((word, vector))
+        self.words_read += 1;
+        self.raw_word = bytes.map(|bytes| raw_span(&bytes, start, end));
+        self.components = line.split_off(end);
+        Ok(Some(line.split_off(start)))
+    }
+
+    fn read_vector(&mut self, vector: &mut [f32]) -> Result<(), Word2VecError> {
+        parse_components(
+            &self.components,
+            vector,
+            self.words_read - 1,
+            self.record_offset,
+            self.strict,
+        )
+    }
+
+    fn skip_vector(&mut self) -> Result<(), Word2VecError> {
+        Ok(())
+    }
+
+    fn raw_word(&self) -> Option<&[u8]> {
+        self.raw_word.as_deref()
+    }
+}
+
+impl<R: BufRead> Iterator for GloveReader<R> {
+    type Item = Record;
+
+    fn next(&mut self) -> Option<Record> {
+        if self.failed {
+            return None;
+        }
+        let record = next_record(self);
+        self.failed = matches!(record, Some(Err(_)));
+        record
     }
 }
@@ -0,0 +1,167 @@
+use std::io::Write;
+
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+
+use errors::Word2VecError;
+use vectorreader::{ElementType, Endianness};
+
+/// Write the `<vocabulary size> <vector size>` header line which starts both the binary and the
+/// text format.
+fn write_header<W: Write>(
+    writer: &mut W,
+    vocabulary_size: usize,
+    vector_size: usize,
+) -> Result<(), Word2VecError> {
+    writeln!(writer, "{} {}", vocabulary_size, vector_size)?;
+    Ok(())
+}
+
+/// Check that a record can be written without corrupting the file layout.
+fn check_record(word: &str, vector: &[f32], vector_size: usize) -> Result<(), Word2VecError> {
+    if word.is_empty() || word.chars().any(char::is_whitespace) {
+        return Err(Word2VecError::InvalidWord(word.to_string()));
+    }
+    if vector.len() != vector_size {
+        return Err(Word2VecError::WrongVectorSize {
+            expected: vector_size,
+            found: vector.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Writer for the binary word2vec format
+///
+/// The output is laid out like the files written by the original word2vec tool: a header line,
+/// then for each word the word itself, a space, the little-endian `f32` components and a line
+/// break. Other component layouts can be produced by setting
+/// [`element_type`](#method.element_type) and [`endianness`](#method.endianness).
+pub struct WordVectorWriter<W: Write> {
+    vector_size: usize,
+    element_type: ElementType,
+    endianness: Endianness,
+    writer: W,
+}
+
+impl<W: Write> WordVectorWriter<W> {
+    pub fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    pub fn new_from_writer(
+        mut writer: W,
+        vocabulary_size: usize,
+        vector_size: usize,
+    ) -> Result<WordVectorWriter<W>, Word2VecError> {
+        write_header(&mut writer, vocabulary_size, vector_size)?;
+        Ok(WordVectorWriter {
+            vector_size,
+            element_type: ElementType::F32,
+            endianness: Endianness::Little,
+            writer,
+        })
+    }
+
+    /// Set the type of the vector components.
+    pub fn element_type(mut self, element_type: ElementType) -> WordVectorWriter<W> {
+        self.element_type = element_type;
+        self
+    }
+
+    /// Set the byte order of the vector components.
+    pub fn endianness(mut self, endianness: Endianness) -> WordVectorWriter<W> {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Append the vector of `word` to the output.
+    pub fn write_vector(&mut self, word: &str, vector: &[f32]) -> Result<(), Word2VecError> {
+        check_record(word, vector, self.vector_size)?;
+        self.writer.write_all(word.as_bytes())?;
+        self.writer.write_all(b" ")?;
+        for &x in vector {
+            match (self.element_type, self.endianness) {
+                (ElementType::F32, Endianness::Little) => {
+                    self.writer.write_f32::<LittleEndian>(x)?
+                }
+                (ElementType::F32, Endianness::Big) => self.writer.write_f32::<BigEndian>(x)?,
+                (ElementType::F64, Endianness::Little) => {
+                    self.writer.write_f64::<LittleEndian>(f64::from(x))?
+                }
+                (ElementType::F64, Endianness::Big) => {
+                    self.writer.write_f64::<BigEndian>(f64::from(x))?
+                }
+                #[cfg(feature = "half")]
+                (ElementType::F16, Endianness::Little) => self
+                    .writer
+                    .write_u16::<LittleEndian>(half::f16::from_f32(x).to_bits())?,
+                #[cfg(feature = "half")]
+                (ElementType::F16, Endianness::Big) => self
+                    .writer
+                    .write_u16::<BigEndian>(half::f16::from_f32(x).to_bits())?,
+                #[cfg(feature = "half")]
+                (ElementType::Bf16, Endianness::Little) => self
+                    .writer
+                    .write_u16::<LittleEndian>(half::bf16::from_f32(x).to_bits())?,
+                #[cfg(feature = "half")]
+                (ElementType::Bf16, Endianness::Big) => self
+                    .writer
+                    .write_u16::<BigEndian>(half::bf16::from_f32(x).to_bits())?,
+            }
+        }
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Flush the output and return the underlying writer.
+    pub fn into_inner(mut self) -> Result<W, Word2VecError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Writer for the plain-text word2vec format
+///
+/// After the header line, each word is written on its own line, followed by its vector
+/// components separated by spaces.
+pub struct TextWordVectorWriter<W: Write> {
+    vector_size: usize,
+    writer: W,
+}
+
+impl<W: Write> TextWordVectorWriter<W> {
+    pub fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    pub fn new_from_writer(
+        mut writer: W,
+        vocabulary_size: usize,
+        vector_size: usize,
+    ) -> Result<TextWordVectorWriter<W>, Word2VecError> {
+        write_header(&mut writer, vocabulary_size, vector_size)?;
+        Ok(TextWordVectorWriter {
+            vector_size,
+            writer,
+        })
+    }
+
+    /// Append the vector of `word` to the output.
+    pub fn write_vector(&mut self, word: &str, vector: &[f32]) -> Result<(), Word2VecError> {
+        check_record(word, vector, self.vector_size)?;
+        self.writer.write_all(word.as_bytes())?;
+        // `Display` for floats prints the shortest representation which parses back to the
+        // same value, so no precision is lost
+        for x in vector {
+            write!(self.writer, " {}", x)?;
+        }
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Flush the output and return the underlying writer.
+    pub fn into_inner(mut self) -> Result<W, Word2VecError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
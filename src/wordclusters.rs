@@ -1,8 +1,7 @@
+use compression;
 use errors::Word2VecError;
 use std::collections::HashMap;
-use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
 
 pub struct WordClusters {
     clusters: HashMap<i32, Vec<String>>,
@@ -10,8 +9,7 @@ pub struct WordClusters {
 
 impl WordClusters {
     pub fn load_from_file(file_name: &str) -> Result<WordClusters, Word2VecError> {
-        let file = File::open(file_name)?;
-        let reader = BufReader::new(file);
+        let reader = compression::open_file(file_name)?;
 
         WordClusters::load_from_reader(reader)
     }
@@ -1,32 +1,107 @@
 use crate::errors::Word2VecError;
+use crate::loader::WordVectorLoader;
+use crate::matrix::Matrix;
+use crate::search::{self, VectorSpace};
+pub use crate::search::{AnalogyMethod, AnalogyResult};
 use crate::utils;
-use crate::vectorreader::WordVectorReader;
-use std::cmp::Ordering;
-use std::collections::hash_map::Keys;
+use crate::vectorwriter::{TextWordVectorWriter, WordVectorWriter};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::BufWriter;
+use std::str;
+
+/// Precision in which a [`WordVector`](struct.WordVector.html) keeps its vectors in memory
+///
+/// Similarity queries compute in `f32` for the floating point precisions, converting the stored
+/// components on the fly. With `Int8`, the query is quantized as well and compared in integer
+/// arithmetic. [`WordVector::reconstruction_error`](struct.WordVector.html#method.reconstruction_error)
+/// tells how much a precision distorts the vectors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Precision {
+    /// Single precision, as the vectors are stored by word2vec
+    #[default]
+    F32,
+    /// IEEE 754 half precision, which halves the memory but keeps only about three significant
+    /// digits; requires the `half` feature
+    #[cfg(feature = "half")]
+    F16,
+    /// bfloat16, which halves the memory and keeps the range of `f32` but only about two
+    /// significant digits; requires the `half` feature
+    #[cfg(feature = "half")]
+    Bf16,
+    /// Signed 8 bit integers with scale factors, which quarters the memory
+    Int8(Scale),
+}
+
+/// Granularity of the scale factors of vectors stored as `Precision::Int8`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Scale {
+    /// One scale factor per vector, so each vector uses the full range of 8 bits
+    #[default]
+    PerVector,
+    /// One scale factor per dimension, which suits dimensions of very different ranges; the
+    /// vectors are quantized once all of them were loaded
+    PerDimension,
+}
+
+/// Distortion of the vectors by storing them in a lower precision, see
+/// [`WordVector::reconstruction_error`](struct.WordVector.html#method.reconstruction_error)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReconstructionError {
+    /// Mean of the squared differences of all components
+    pub mean_squared: f32,
+    /// Largest absolute difference of any component
+    pub max_absolute: f32,
+}
 
 /// Representation of a word vector space
 ///
 /// Each word of a vocabulary is represented by a vector. All words span a vector space. This data
 /// structure manages this vector space of words.
+///
+/// The vectors are stored as rows of a single row-major matrix, in the order in which the words
+/// appear in the source file. Rows can be addressed by their index, see
+/// [`index_of`](#method.index_of). Since word2vec sorts its output by corpus frequency, the
+/// index of a word is also its frequency rank.
+///
+/// The matrix holds normalized vectors, by default as `f32`, see [`Precision`](enum.Precision.html).
+/// The unnormalized vectors and their norms are only kept if requested from the
+/// [`WordVectorLoader`](../loader/struct.WordVectorLoader.html).
 pub struct WordVector {
-    vocabulary: HashMap<String, Vec<f32>>,
+    words: Vec<String>,
+    indices: HashMap<String, usize>,
+    byte_indices: HashMap<Vec<u8>, usize>,
+    matrix: Matrix,
+    raw: Option<Vec<f32>>,
+    norms: Option<Vec<f32>>,
     vector_size: usize,
 }
 
 impl WordVector {
+    /// Create a [`WordVectorLoader`](../loader/struct.WordVectorLoader.html) to load a word
+    /// vector space with options, such as a limit on the number of words.
+    pub fn loader() -> WordVectorLoader {
+        WordVectorLoader::new()
+    }
+
+    /// Load a word vector space from a file of unknown format
+    ///
+    /// The format is detected from the header and the first record of the file, see
+    /// [`detect_format`](../vectorreader/fn.detect_format.html). If the format can't be
+    /// determined, `Word2VecError::UnknownFormat` describes why.
+    pub fn load(file_name: &str) -> Result<WordVector, Word2VecError> {
+        WordVectorLoader::new().load(file_name)
+    }
+
     /// Load a word vector space from file
     ///
     /// Word2vec is able to store the word vectors in a binary file. This function parses the file
-    /// and loads the vectors into RAM.
+    /// and loads the vectors into RAM. Files compressed with gzip, bzip2 or xz are decompressed
+    /// while reading if the corresponding cargo feature is enabled.
     pub fn load_from_binary(file_name: &str) -> Result<WordVector, Word2VecError> {
-        let file = File::open(file_name)?;
-        let reader = BufReader::new(file);
-
-        WordVector::load_from_reader(reader)
+        WordVectorLoader::new().load_from_binary(file_name)
     }
 
     /// Load a word vector space from a reader
@@ -34,27 +109,241 @@ impl WordVector {
     /// Word2vec is able to store the word vectors in a binary format. This function parses the bytes in that format
     /// and loads the vectors into RAM.
     pub fn load_from_reader<R: BufRead>(reader: R) -> Result<WordVector, Word2VecError> {
-        let reader = WordVectorReader::new_from_reader(reader)?;
-        let vector_size = reader.vector_size();
+        WordVectorLoader::new().load_from_reader(reader)
+    }
 
-        let mut vocabulary: HashMap<String, Vec<f32>> =
-            HashMap::with_capacity(reader.vocabulary_size());
-        for item in reader {
-            let (word, mut vector) = item;
-            utils::vector_norm(&mut vector);
+    /// Load a word vector space from a file in text format
+    ///
+    /// Besides the binary format, word2vec can store the word vectors as plain text, one word per
+    /// line followed by its vector components. This function parses such a file and loads the
+    /// vectors into RAM.
+    pub fn load_from_text(file_name: &str) -> Result<WordVector, Word2VecError> {
+        WordVectorLoader::new().load_from_text(file_name)
+    }
 
-            vocabulary.insert(word, vector);
-        }
+    /// Load a word vector space in text format from a reader
+    ///
+    /// See [`load_from_text`](#method.load_from_text) for a description of the format.
+    pub fn load_from_text_reader<R: BufRead>(reader: R) -> Result<WordVector, Word2VecError> {
+        WordVectorLoader::new().load_from_text_reader(reader)
+    }
+
+    /// Load a word vector space from a file in GloVe format
+    ///
+    /// GloVe stores its vectors like the text format of word2vec, but without the header line.
+    /// The vector size is inferred from the first line of the file.
+    pub fn load_from_glove(file_name: &str) -> Result<WordVector, Word2VecError> {
+        WordVectorLoader::new().load_from_glove(file_name)
+    }
+
+    /// Load a word vector space in GloVe format from a reader
+    ///
+    /// See [`load_from_glove`](#method.load_from_glove) for a description of the format.
+    pub fn load_from_glove_reader<R: BufRead>(reader: R) -> Result<WordVector, Word2VecError> {
+        WordVectorLoader::new().load_from_glove_reader(reader)
+    }
 
-        Ok(WordVector {
-            vocabulary,
+    pub(crate) fn from_parts(
+        words: Vec<String>,
+        indices: HashMap<String, usize>,
+        byte_indices: HashMap<Vec<u8>, usize>,
+        matrix: Matrix,
+        raw: Option<Vec<f32>>,
+        norms: Option<Vec<f32>>,
+        vector_size: usize,
+    ) -> WordVector {
+        WordVector {
+            words,
+            indices,
+            byte_indices,
+            matrix,
+            raw,
+            norms,
             vector_size,
-        })
+        }
+    }
+
+    /// Save the word vector space to a file in binary format
+    ///
+    /// The file can be read by the original word2vec tool, gensim and
+    /// [`load_from_binary`](#method.load_from_binary). Note that the vectors are stored
+    /// normalized, as they are kept in RAM, unless the raw vectors were kept while loading.
+    pub fn save_binary(&self, file_name: &str) -> Result<(), Word2VecError> {
+        let file = File::create(file_name)?;
+        self.write_binary(BufWriter::new(file))
+    }
+
+    /// Write the word vector space in binary format to a writer
+    pub fn write_binary<W: Write>(&self, writer: W) -> Result<(), Word2VecError> {
+        let mut writer =
+            WordVectorWriter::new_from_writer(writer, self.word_count(), self.vector_size)?;
+        for (word, vector) in self.words.iter().zip(self.row_iter()) {
+            writer.write_vector(word, &vector)?;
+        }
+        writer.into_inner()?;
+        Ok(())
+    }
+
+    /// Save the word vector space to a file in text format
+    ///
+    /// See [`save_binary`](#method.save_binary) for details.
+    pub fn save_text(&self, file_name: &str) -> Result<(), Word2VecError> {
+        let file = File::create(file_name)?;
+        self.write_text(BufWriter::new(file))
+    }
+
+    /// Write the word vector space in text format to a writer
+    pub fn write_text<W: Write>(&self, writer: W) -> Result<(), Word2VecError> {
+        let mut writer =
+            TextWordVectorWriter::new_from_writer(writer, self.word_count(), self.vector_size)?;
+        for (word, vector) in self.words.iter().zip(self.row_iter()) {
+            writer.write_vector(word, &vector)?;
+        }
+        writer.into_inner()?;
+        Ok(())
     }
 
     /// Get word vector for the given word.
-    pub fn get_vector(&self, word: &str) -> Option<&Vec<f32>> {
-        self.vocabulary.get(word)
+    ///
+    /// The vector is borrowed from the matrix if it is stored as `f32` and converted otherwise.
+    pub fn get_vector(&self, word: &str) -> Option<Cow<'_, [f32]>> {
+        self.index_of(word).map(|index| self.vector(index))
+    }
+
+    /// Get the word vector for the given word as stored in the source file, before normalization
+    ///
+    /// Returns `None` if the raw vectors were not kept while loading, see
+    /// [`WordVectorLoader::keep_raw_vectors`](../loader/struct.WordVectorLoader.html#method.keep_raw_vectors).
+    pub fn get_vector_raw(&self, word: &str) -> Option<&[f32]> {
+        let index = self.index_of(word)?;
+        self.raw
+            .as_ref()
+            .map(|raw| &raw[index * self.vector_size..(index + 1) * self.vector_size])
+    }
+
+    /// Get the length of the word vector for the given word before normalization
+    ///
+    /// Returns `None` if the norms were not kept while loading, see
+    /// [`WordVectorLoader::keep_norms`](../loader/struct.WordVectorLoader.html#method.keep_norms).
+    pub fn get_norm(&self, word: &str) -> Option<f32> {
+        let index = self.index_of(word)?;
+        self.norms.as_ref().map(|norms| norms[index])
+    }
+
+    /// Get the row index of the given word.
+    pub fn index_of(&self, word: &str) -> Option<usize> {
+        self.indices.get(word).cloned()
+    }
+
+    /// Get the row index of the given word, given as bytes
+    ///
+    /// Unlike [`index_of`](#method.index_of), this also finds words which are not valid UTF-8 and
+    /// were kept by loading with `Utf8Policy::Raw`, see
+    /// [`WordVectorLoader::utf8_policy`](../loader/struct.WordVectorLoader.html#method.utf8_policy).
+    pub fn index_of_bytes(&self, word: &[u8]) -> Option<usize> {
+        match str::from_utf8(word) {
+            Ok(word) => self.index_of(word),
+            Err(_) => self.byte_indices.get(word).cloned(),
+        }
+    }
+
+    /// Get word vector for the given word, given as bytes, see
+    /// [`index_of_bytes`](#method.index_of_bytes).
+    pub fn get_vector_bytes(&self, word: &[u8]) -> Option<Cow<'_, [f32]>> {
+        self.index_of_bytes(word).map(|index| self.vector(index))
+    }
+
+    /// Get the frequency rank of the given word
+    ///
+    /// The most frequent word has rank 0. The rank is taken from the position of the word in the
+    /// source file, which word2vec sorts by frequency, and equals [`index_of`](#method.index_of).
+    pub fn rank(&self, word: &str) -> Option<usize> {
+        self.index_of(word)
+    }
+
+    /// Get the word stored at the given row index.
+    pub fn word_at(&self, index: usize) -> Option<&str> {
+        self.words.get(index).map(|word| word.as_str())
+    }
+
+    /// Get the word vector stored at the given row index.
+    pub fn vector_at(&self, index: usize) -> Option<Cow<'_, [f32]>> {
+        if index < self.words.len() {
+            Some(self.vector(index))
+        } else {
+            None
+        }
+    }
+
+    /// Get the matrix of all word vectors
+    ///
+    /// The matrix is stored row-major, with one row of
+    /// [`get_col_count`](#method.get_col_count) components per word. Unless the vectors are
+    /// stored as `f32`, this converts the whole matrix.
+    pub fn matrix(&self) -> Cow<'_, [f32]> {
+        self.matrix.components()
+    }
+
+    /// Get the precision in which the vectors are stored.
+    pub fn precision(&self) -> Precision {
+        self.matrix.precision()
+    }
+
+    /// Measure how much storing the vectors in `precision` distorts them
+    ///
+    /// The normalized vectors, as currently stored, are converted to `precision` and back and
+    /// compared to themselves. Since vectors have unit length, the errors are relative to it.
+    pub fn reconstruction_error(&self, precision: Precision) -> ReconstructionError {
+        let original = self.matrix.components();
+        let converted = self.matrix.convert(precision);
+        let converted = converted.components();
+        let (sum, max) =
+            original
+                .iter()
+                .zip(converted.iter())
+                .fold((0f64, 0f32), |(sum, max), (x, y)| {
+                    let difference = (x - y).abs();
+                    (
+                        sum + f64::from(difference * difference),
+                        max.max(difference),
+                    )
+                });
+        let mean_squared = if original.is_empty() {
+            0.0
+        } else {
+            (sum / original.len() as f64) as f32
+        };
+        ReconstructionError {
+            mean_squared,
+            max_absolute: max,
+        }
+    }
+
+    /// Convert the stored vectors to `precision`
+    ///
+    /// Raw vectors and norms, if they were kept, are left untouched.
+    pub fn with_precision(mut self, precision: Precision) -> WordVector {
+        if self.matrix.precision() != precision {
+            self.matrix = self.matrix.convert(precision);
+        }
+        self
+    }
+
+    /// Iterate over the rows which are saved, the raw vectors if they were kept.
+    fn row_iter(&self) -> impl Iterator<Item = Cow<'_, [f32]>> {
+        (0..self.words.len()).map(move |index| match self.raw {
+            Some(ref raw) => {
+                Cow::Borrowed(&raw[index * self.vector_size..(index + 1) * self.vector_size])
+            }
+            None => self.matrix.row(index),
+        })
+    }
+
+    /// Compute the cosine similarity of two words, `None` if one of them is unknown.
+    pub fn similarity(&self, word1: &str, word2: &str) -> Option<f32> {
+        let vector1 = self.get_vector(word1)?;
+        let vector2 = self.get_vector(word2)?;
+        Some(utils::dot_product(&vector1, &vector2))
     }
 
     /// Compute consine distance to similar words.
@@ -63,64 +352,111 @@ impl WordVector {
     /// other. This method calculates the `n` closest words via the cosine of the requested word to
     /// all other words.
     pub fn cosine(&self, word: &str, n: usize) -> Option<Vec<(String, f32)>> {
-        let word_vector = self.vocabulary.get(word);
-        match word_vector {
-            Some(val) => {
-                // save index and cosine distance to current word
-                let mut metrics: Vec<(String, f32)> = self
-                    .vocabulary
-                    .iter()
-                    .map(|(i, other_val)| (i.to_owned(), utils::dot_product(other_val, val)))
-                    .collect();
-                metrics.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
-                Some(metrics[1..n + 1].iter().map(|v| v.to_owned()).collect())
-            }
-            None => None,
-        }
+        self.cosine_restricted(word, n, self.word_count())
     }
 
-    pub fn analogy(&self, pos: Vec<&str>, neg: Vec<&str>, n: usize) -> Option<Vec<(String, f32)>> {
-        let mut vectors: Vec<Vec<f32>> = Vec::new();
-        let mut exclude: Vec<String> = Vec::new();
-        for word in pos {
-            exclude.push(word.to_string());
-            match self.vocabulary.get(word) {
-                Some(val) => vectors.push(val.to_owned()),
-                None => {}
-            }
-        }
-        for word in neg.iter() {
-            exclude.push(word.to_string());
-            match self.vocabulary.get(word.to_owned()) {
-                Some(val) => vectors.push(val.iter().map(|x| -x).collect::<Vec<f32>>()),
-                None => {}
-            }
-        }
-        if exclude.is_empty() {
-            return None;
-        }
-        let mut mean: Vec<f32> = Vec::with_capacity(self.vector_size);
-        for i in 0..self.vector_size {
-            mean.push(utils::mean(vectors.iter().map(|v| v[i])));
-        }
-        let mut metrics: Vec<(&String, f32)> = Vec::new();
-        for word in self.vocabulary.iter() {
-            metrics.push((word.0, utils::dot_product(word.1, &mean)));
-        }
-        metrics.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
-        metrics.retain(|x| !exclude.contains(x.0));
-        Some(
-            metrics
-                .iter()
-                .take(n)
-                .map(|&(x, y)| (x.clone(), y))
-                .collect(),
-        )
+    /// Compute cosine distance to similar words among the most frequent ones.
+    ///
+    /// Like [`cosine`](#method.cosine), but only the `restrict_vocab` most frequent words are
+    /// considered as results. The requested word itself may have any rank.
+    pub fn cosine_restricted(
+        &self,
+        word: &str,
+        n: usize,
+        restrict_vocab: usize,
+    ) -> Option<Vec<(String, f32)>> {
+        search::cosine(self, word, n, restrict_vocab)
+    }
+
+    /// Find the `n` words closest to an arbitrary vector
+    ///
+    /// `vector` may be any vector of [`get_col_count`](#method.get_col_count) components, such as
+    /// the average of several word vectors or a cluster centroid; it is normalized before the
+    /// words are compared with it by cosine similarity. The words in `exclude`, for example the
+    /// ones the vector was computed from, are left out of the result. Vectors of the wrong size
+    /// or with NaN or infinite components are rejected.
+    pub fn similar_by_vector(
+        &self,
+        vector: &[f32],
+        n: usize,
+        exclude: &[&str],
+    ) -> Result<Vec<(String, f32)>, Word2VecError> {
+        search::similar_by_vector(self, vector, n, exclude, self.word_count())
+    }
+
+    /// Compute the `n` closest words for each of many words at once
+    ///
+    /// The result holds the answer of [`cosine`](#method.cosine) for each of `words`, in the same
+    /// order, but the vocabulary is scanned once per block of words instead of once per word.
+    /// With the `parallel` feature, the blocks are processed on all cores. Scores are computed
+    /// in `f32`, also for quantized vectors, so they may differ slightly from `cosine` then.
+    pub fn cosine_batch(&self, words: &[&str], n: usize) -> Vec<Option<Vec<(String, f32)>>> {
+        search::cosine_batch(self, words, n, self.word_count())
+    }
+
+    /// Solve an analogy, finding the words closest to the mean of the `pos` vectors and the
+    /// negated `neg` vectors
+    ///
+    /// "man is to king as woman is to ?" is asked for by `pos = ["king", "woman"]` and
+    /// `neg = ["man"]`. Words which are not in the vocabulary are left out and listed in the
+    /// result. If no words are given, `Word2VecError::InvalidParameter` is returned, and if none
+    /// of them are known, `Word2VecError::UnknownWords`.
+    pub fn analogy(
+        &self,
+        pos: Vec<&str>,
+        neg: Vec<&str>,
+        n: usize,
+    ) -> Result<AnalogyResult, Word2VecError> {
+        self.analogy_restricted(pos, neg, n, self.word_count())
+    }
+
+    /// Solve an analogy among the most frequent words.
+    ///
+    /// Like [`analogy`](#method.analogy), but only the `restrict_vocab` most frequent words are
+    /// considered as results. The given words themselves may have any rank.
+    pub fn analogy_restricted(
+        &self,
+        pos: Vec<&str>,
+        neg: Vec<&str>,
+        n: usize,
+        restrict_vocab: usize,
+    ) -> Result<AnalogyResult, Word2VecError> {
+        self.analogy_with_restricted(AnalogyMethod::CosAdd, pos, neg, n, restrict_vocab)
+    }
+
+    /// Solve an analogy with the given scoring method
+    ///
+    /// Like [`analogy`](#method.analogy), which uses `AnalogyMethod::CosAdd`, but the candidates
+    /// are scored by `method`. `AnalogyMethod::PairDirection` fails with
+    /// `Word2VecError::InvalidParameter` unless given two `pos` words and one `neg` word, and
+    /// with `Word2VecError::UnknownWords` unless all of them are known.
+    pub fn analogy_with(
+        &self,
+        method: AnalogyMethod,
+        pos: Vec<&str>,
+        neg: Vec<&str>,
+        n: usize,
+    ) -> Result<AnalogyResult, Word2VecError> {
+        self.analogy_with_restricted(method, pos, neg, n, self.word_count())
+    }
+
+    /// Solve an analogy with the given scoring method among the most frequent words, see
+    /// [`analogy_with`](#method.analogy_with) and
+    /// [`analogy_restricted`](#method.analogy_restricted).
+    pub fn analogy_with_restricted(
+        &self,
+        method: AnalogyMethod,
+        pos: Vec<&str>,
+        neg: Vec<&str>,
+        n: usize,
+        restrict_vocab: usize,
+    ) -> Result<AnalogyResult, Word2VecError> {
+        search::analogy(self, method, &pos, &neg, n, restrict_vocab)
     }
 
     /// Get the number of all known words from the vocabulary.
     pub fn word_count(&self) -> usize {
-        self.vocabulary.len()
+        self.words.len()
     }
 
     /// Return the number of columns of the word vector.
@@ -128,20 +464,48 @@ impl WordVector {
         self.vector_size // size == column count
     }
 
-    /// Get all known words from the vocabulary.
-    pub fn get_words(&self) -> Words {
-        Words::new(&self.vocabulary)
+    /// Get all known words from the vocabulary, in the order of the source file.
+    ///
+    /// For files written by word2vec, this is the order of descending corpus frequency.
+    pub fn get_words(&self) -> Words<'_> {
+        Words::new(&self.words)
+    }
+}
+
+impl VectorSpace for WordVector {
+    fn vector_size(&self) -> usize {
+        self.vector_size
+    }
+
+    fn rows(&self) -> usize {
+        self.words.len()
+    }
+
+    fn vector(&self, index: usize) -> Cow<'_, [f32]> {
+        self.matrix.row(index)
+    }
+
+    fn dots(&self, query: &[f32], rows: usize) -> Vec<f32> {
+        self.matrix.dots(query, rows)
+    }
+
+    fn word(&self, index: usize) -> &str {
+        &self.words[index]
+    }
+
+    fn index_of(&self, word: &str) -> Option<usize> {
+        WordVector::index_of(self, word)
     }
 }
 
 #[derive(Debug)]
 pub struct Words<'parent> {
-    iter: Keys<'parent, String, Vec<f32>>,
+    iter: std::slice::Iter<'parent, String>,
 }
 
 impl<'a> Words<'a> {
-    fn new(x: &'a HashMap<String, Vec<f32>>) -> Words<'a> {
-        Words { iter: x.keys() }
+    pub(crate) fn new(x: &'a [String]) -> Words<'a> {
+        Words { iter: x.iter() }
     }
 }
 
@@ -1,10 +1,34 @@
+#![allow(clippy::vec_init_then_push)]
+#![feature(test)]
+#[cfg(feature = "bzip2")]
+extern crate bzip2;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+extern crate test;
 extern crate word2vec;
-use word2vec::vectorreader::WordVectorReader;
+#[cfg(feature = "xz")]
+extern crate xz2;
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+use test::Bencher;
+use word2vec::errors::Word2VecError;
+use word2vec::vectorreader::{
+    detect_format, ElementType, Endianness, GloveReader, TextWordVectorReader, Utf8Policy,
+    VectorFormat, WordVectorReader,
+};
+use word2vec::vectorwriter::{TextWordVectorWriter, WordVectorWriter};
 use word2vec::wordvectors::WordVector;
 use word2vec::wordvectors::Words;
 
 const PATH: &str = "vectors.bin";
 
+const TEXT_MODEL: &str = "4 4
+snow 1.0 0.0 0.0 0.0
+ice 0.9 0.1 0.0 0.0
+sun 0.0 0.0 1.0 0.0
+rain 0.5 0.5 0.0 0.5
+";
+
 #[test]
 fn test_word_cosine() {
     let model = WordVector::load_from_binary(PATH).unwrap();
@@ -23,6 +47,19 @@ fn test_nonexisting_word_cosine() {
     assert!(result.is_none());
 }
 
+#[test]
+fn test_zero_vector_cosine() {
+    let text = "3 4\nsnow 1.0 0.0 0.0 0.0\nice 0.9 0.1 0.0 0.0\nzero 0.0 0.0 0.0 0.0\n";
+    let model = WordVector::load_from_text_reader(Cursor::new(text)).unwrap();
+    assert_eq!(&*model.get_vector("zero").unwrap(), &[0.0; 4][..]);
+    let res = model.cosine("snow", 10).unwrap();
+    assert_eq!(res.len(), 2);
+    assert_eq!(res[0].0, "ice");
+    assert!(res.iter().all(|&(_, similarity)| similarity.is_finite()));
+    let res = model.cosine("zero", 10).unwrap();
+    assert!(res.iter().all(|&(_, similarity)| similarity == 0.0));
+}
+
 #[test]
 fn test_word_analogy() {
     let model = WordVector::load_from_binary(PATH).unwrap();
@@ -33,7 +70,8 @@ fn test_word_analogy() {
     neg.push("man");
     let res = model
         .analogy(pos, neg, 10)
-        .expect("couldn't find all of the given words");
+        .expect("couldn't find all of the given words")
+        .words;
     assert_eq!(res.len(), 10);
     let only_words: Vec<&str> = res.iter().map(|x| x.0.as_ref()).collect();
     assert!(!only_words.contains(&"woman"));
@@ -44,8 +82,10 @@ fn test_word_analogy() {
 #[test]
 fn test_word_analogy_with_empty_params() {
     let model = WordVector::load_from_binary(PATH).unwrap();
-    let result = model.analogy(Vec::new(), Vec::new(), 10);
-    assert!(result.is_none());
+    match model.analogy(Vec::new(), Vec::new(), 10) {
+        Err(Word2VecError::InvalidParameter(_)) => {}
+        _ => panic!("analogy without words was answered"),
+    }
 }
 
 #[test]
@@ -60,6 +100,35 @@ fn test_words() {
     assert_eq!(v.get_words().count(), 71291);
 }
 
+/// Path of a scratch file in the system's temporary directory, unique per test process.
+fn temp_path(name: &str) -> String {
+    let mut path = std::env::temp_dir();
+    path.push(format!("word2vec-{}-{}", std::process::id(), name));
+    path.to_str().unwrap().to_string()
+}
+
+/// A text model of `words` words named `w0`, `w1`, ... with pseudo-random vectors.
+fn synthetic_model(words: usize, vector_size: usize) -> String {
+    let mut model = format!("{} {}\n", words, vector_size);
+    let mut state = 1u32;
+    for i in 0..words {
+        model.push_str(&format!("w{}", i));
+        for _ in 0..vector_size {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            model.push_str(&format!(" {}", f64::from(state >> 16) / 32768.0 - 1.0));
+        }
+        model.push('\n');
+    }
+    model
+}
+
+fn assert_vectors_close(left: &[f32], right: &[f32]) {
+    assert_eq!(left.len(), right.len());
+    for (l, r) in left.iter().zip(right.iter()) {
+        assert!((l - r).abs() < 1e-6, "{:?} != {:?}", left, right);
+    }
+}
+
 fn is_normal<T: Sized + Send + Unpin>() {}
 
 #[test]
@@ -68,3 +137,1285 @@ fn normal_types() {
     is_normal::<WordVectorReader<std::io::BufReader<std::fs::File>>>();
     is_normal::<Words>();
 }
+
+#[test]
+fn test_text_reader_parses_header_and_records() {
+    let reader = TextWordVectorReader::new_from_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    assert_eq!(reader.vocabulary_size(), 4);
+    assert_eq!(reader.vector_size(), 4);
+    let records: Vec<(String, Vec<f32>)> = reader.collect::<Result<_, _>>().unwrap();
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[1].0, "ice");
+    assert_eq!(records[1].1, vec![0.9, 0.1, 0.0, 0.0]);
+}
+
+#[test]
+fn test_text_reader_rejects_missing_header() {
+    let result = TextWordVectorReader::new_from_reader(Cursor::new("snow 1.0 0.0\n"));
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_load_from_text_reader() {
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    assert_eq!(model.word_count(), 4);
+    assert_eq!(model.get_col_count(), 4);
+    let res = model.cosine("snow", 1).unwrap();
+    assert_eq!(res[0].0, "ice");
+}
+
+#[test]
+fn test_binary_writer_layout() {
+    let mut writer = WordVectorWriter::new_from_writer(Vec::new(), 1, 2).unwrap();
+    writer.write_vector("snow", &[1.0, -2.0]).unwrap();
+    let bytes = writer.into_inner().unwrap();
+    let mut expected = b"1 2\nsnow ".to_vec();
+    expected.extend_from_slice(&1.0f32.to_le_bytes());
+    expected.extend_from_slice(&(-2.0f32).to_le_bytes());
+    expected.push(b'\n');
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn test_writer_rejects_invalid_records() {
+    let mut writer = TextWordVectorWriter::new_from_writer(Vec::new(), 1, 2).unwrap();
+    assert!(writer.write_vector("snow", &[1.0]).is_err());
+    assert!(writer.write_vector("new york", &[1.0, 2.0]).is_err());
+}
+
+#[test]
+fn test_binary_round_trip() {
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let mut bytes = Vec::new();
+    model.write_binary(&mut bytes).unwrap();
+    let loaded = WordVector::load_from_reader(Cursor::new(bytes)).unwrap();
+    assert_eq!(loaded.word_count(), model.word_count());
+    for word in model.get_words() {
+        assert_vectors_close(
+            &loaded.get_vector(word).unwrap(),
+            &model.get_vector(word).unwrap(),
+        );
+    }
+}
+
+#[test]
+fn test_text_round_trip() {
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let mut bytes = Vec::new();
+    model.write_text(&mut bytes).unwrap();
+    let loaded = WordVector::load_from_text_reader(Cursor::new(bytes)).unwrap();
+    assert_eq!(loaded.word_count(), model.word_count());
+    for word in model.get_words() {
+        assert_vectors_close(
+            &loaded.get_vector(word).unwrap(),
+            &model.get_vector(word).unwrap(),
+        );
+    }
+}
+
+#[test]
+fn test_detect_text_format() {
+    let (format, mut reader) = detect_format(Cursor::new(TEXT_MODEL)).unwrap();
+    assert_eq!(format, VectorFormat::Text);
+    let mut content = String::new();
+    reader.read_to_string(&mut content).unwrap();
+    assert_eq!(content, TEXT_MODEL);
+}
+
+#[test]
+fn test_detect_binary_format() {
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let mut bytes = Vec::new();
+    model.write_binary(&mut bytes).unwrap();
+    let (format, reader) = detect_format(Cursor::new(bytes)).unwrap();
+    assert_eq!(format, VectorFormat::Binary);
+    let loaded = WordVector::load_from_reader(reader).unwrap();
+    assert_eq!(loaded.word_count(), 4);
+}
+
+#[test]
+fn test_detect_format_reports_dimension_mismatch() {
+    let result = detect_format(Cursor::new("1 3\nsnow 1.0 0.0\n"));
+    match result {
+        Err(Word2VecError::UnknownFormat(reason)) => assert!(reason.contains("3")),
+        _ => panic!("expected an UnknownFormat error"),
+    }
+}
+
+#[test]
+fn test_detect_format_rejects_overflowing_vector_size() {
+    let result = detect_format(Cursor::new(
+        &b"1 5000000000000000000\nab \x01\x02\x03\x04"[..],
+    ));
+    match result {
+        Err(Word2VecError::UnknownFormat(reason)) => {
+            assert!(reason.contains("5000000000000000000"))
+        }
+        _ => panic!("expected an UnknownFormat error"),
+    }
+}
+
+#[test]
+fn test_detect_format_of_empty_model() {
+    for input in &["0 4\n", "0 4\n  ", "0 4\n \n"] {
+        let (format, _) = detect_format(Cursor::new(input.as_bytes())).unwrap();
+        assert_eq!(format, VectorFormat::Text);
+    }
+    let path = temp_path("empty_model.txt");
+    std::fs::write(&path, "0 4\n").unwrap();
+    let model = WordVector::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(model.word_count(), 0);
+}
+
+const GLOVE_MODEL: &str = "snow 1.0 0.0 0.0 0.0
+ice 0.9 0.1 0.0 0.0
+. . . 0.0 0.0 1.0 0.0
+";
+
+#[test]
+fn test_glove_reader_infers_vector_size() {
+    let mut reader = GloveReader::new_from_reader(Cursor::new(GLOVE_MODEL)).unwrap();
+    assert_eq!(reader.vector_size(), 4);
+    let records: Vec<(String, Vec<f32>)> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+    assert_eq!(reader.words_read(), 3);
+    assert_eq!(records[0].0, "snow");
+    assert_eq!(records[2].0, ". . .");
+    assert_eq!(records[2].1, vec![0.0, 0.0, 1.0, 0.0]);
+}
+
+#[test]
+fn test_load_glove_and_detect_format() {
+    let model = WordVector::load_from_glove_reader(Cursor::new(GLOVE_MODEL)).unwrap();
+    assert_eq!(model.word_count(), 3);
+    assert_eq!(model.cosine("snow", 1).unwrap()[0].0, "ice");
+
+    let (format, _) = detect_format(Cursor::new(GLOVE_MODEL)).unwrap();
+    assert_eq!(format, VectorFormat::Glove);
+}
+
+#[cfg(not(feature = "gzip"))]
+#[test]
+fn test_compressed_file_without_feature() {
+    let path = temp_path("vectors.txt.gz");
+    std::fs::write(&path, [0x1f, 0x8b, 0x08, 0x00]).unwrap();
+    let result = WordVector::load_from_text(&path);
+    std::fs::remove_file(&path).unwrap();
+    match result {
+        Err(Word2VecError::UnsupportedCompression(format)) => assert_eq!(format, "gzip"),
+        _ => panic!("expected an UnsupportedCompression error"),
+    }
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_load_gzip_compressed_file() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let path = temp_path("vectors.bin.gz");
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    model.write_binary(&mut encoder).unwrap();
+    encoder.flush().unwrap();
+    std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+    let binary = WordVector::load_from_binary(&path);
+    let detected = WordVector::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(binary.unwrap().word_count(), 4);
+    assert_eq!(detected.unwrap().word_count(), 4);
+}
+
+#[cfg(feature = "bzip2")]
+#[test]
+fn test_load_bzip2_compressed_file() {
+    use bzip2::write::BzEncoder;
+    use bzip2::Compression;
+
+    let path = temp_path("vectors.bin.bz2");
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let mut encoder = BzEncoder::new(Vec::new(), Compression::default());
+    model.write_binary(&mut encoder).unwrap();
+    std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+    let binary = WordVector::load_from_binary(&path);
+    let detected = WordVector::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(binary.unwrap().word_count(), 4);
+    assert_eq!(detected.unwrap().word_count(), 4);
+}
+
+#[cfg(feature = "xz")]
+#[test]
+fn test_load_xz_compressed_file() {
+    use xz2::write::XzEncoder;
+
+    let path = temp_path("vectors.txt.xz");
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let mut encoder = XzEncoder::new(Vec::new(), 6);
+    model.write_text(&mut encoder).unwrap();
+    std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+    let text = WordVector::load_from_text(&path);
+    let detected = WordVector::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(text.unwrap().word_count(), 4);
+    assert_eq!(detected.unwrap().word_count(), 4);
+}
+
+#[test]
+fn test_load_text_starting_with_bzip2_signature() {
+    let path = temp_path("bzh.txt");
+    std::fs::write(&path, "BZhx 1.0 0.0 0.0 0.0\nsnow 0.9 0.1 0.0 0.0\n").unwrap();
+    let model = WordVector::load_from_glove(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(model.unwrap().word_count(), 2);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mapped_word_vector() {
+    use word2vec::mappedvectors::MappedWordVector;
+
+    let path = temp_path("vectors.mmap");
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    MappedWordVector::save(&model, &path).unwrap();
+    let mapped = MappedWordVector::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(mapped.word_count(), 4);
+    assert_eq!(mapped.get_col_count(), 4);
+    for word in model.get_words() {
+        assert_eq!(mapped.get_vector(word), model.get_vector(word).as_deref());
+    }
+    assert!(mapped.get_vector("hail").is_none());
+    assert_eq!(mapped.cosine("snow", 1), model.cosine("snow", 1));
+    assert_eq!(
+        mapped.analogy(vec!["ice"], vec!["snow"], 2).unwrap(),
+        model.analogy(vec!["ice"], vec!["snow"], 2).unwrap()
+    );
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mapped_word_vector_rejects_other_files() {
+    use word2vec::mappedvectors::MappedWordVector;
+
+    let path = temp_path("vectors.not-mmap");
+    std::fs::write(&path, TEXT_MODEL).unwrap();
+    let result = MappedWordVector::open(&path);
+    std::fs::remove_file(&path).unwrap();
+    match result {
+        Err(Word2VecError::InvalidMappedFile(_)) => {}
+        _ => panic!("expected an InvalidMappedFile error"),
+    }
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mapped_word_vector_rejects_corrupt_sections() {
+    use word2vec::mappedvectors::MappedWordVector;
+
+    let path = temp_path("vectors.corrupt-mmap");
+    let model =
+        WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL.replace("snow", "snö"))).unwrap();
+    MappedWordVector::save(&model, &path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    let header = |offset: usize| {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(&bytes[offset..offset + 8]);
+        u64::from_le_bytes(buf) as usize
+    };
+    let (offsets, sorted) = (header(32), header(40));
+
+    let corruptions: Vec<(usize, Vec<u8>)> = vec![
+        // a vocabulary size whose offset table size overflows
+        (16, (u64::MAX / 4).to_le_bytes().to_vec()),
+        // a row number beyond the vocabulary in the sorted index
+        (sorted, 4u32.to_le_bytes().to_vec()),
+        // a descending word offset
+        (offsets + 8, 100u64.to_le_bytes().to_vec()),
+        // an offset in the middle of "ö"
+        (offsets + 8, 3u64.to_le_bytes().to_vec()),
+    ];
+    for (offset, patch) in corruptions {
+        let mut corrupt = bytes.clone();
+        corrupt[offset..offset + patch.len()].copy_from_slice(&patch);
+        std::fs::write(&path, &corrupt).unwrap();
+        match MappedWordVector::open(&path) {
+            Err(Word2VecError::InvalidMappedFile(_)) => {}
+            _ => panic!("corrupt file at offset {} was accepted", offset),
+        }
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_index_accessors_follow_file_order() {
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let words: Vec<&String> = model.get_words().collect();
+    assert_eq!(words, vec!["snow", "ice", "sun", "rain"]);
+    assert_eq!(model.index_of("sun"), Some(2));
+    assert_eq!(model.word_at(2), Some("sun"));
+    assert_eq!(model.word_at(4), None);
+    assert_eq!(
+        model.vector_at(2).as_deref(),
+        Some(&[0.0, 0.0, 1.0, 0.0][..])
+    );
+    assert_eq!(model.vector_at(2), model.get_vector("sun"));
+    assert_eq!(model.matrix().len(), 16);
+    assert_eq!(&model.matrix()[8..12], &model.vector_at(2).unwrap()[..]);
+}
+
+#[test]
+fn test_duplicate_words_keep_first_row() {
+    let text = "2 4\nsnow 1.0 0.0 0.0 0.0\nsnow 0.0 1.0 0.0 0.0\n";
+    let model = WordVector::load_from_text_reader(Cursor::new(text)).unwrap();
+    assert_eq!(model.word_count(), 1);
+    assert_eq!(
+        model.get_vector("snow").as_deref(),
+        Some(&[0.0, 1.0, 0.0, 0.0][..])
+    );
+}
+
+#[test]
+fn test_rank_and_restricted_queries() {
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    assert_eq!(model.rank("snow"), Some(0));
+    assert_eq!(model.rank("rain"), Some(3));
+    assert_eq!(model.rank("hail"), None);
+
+    // "ice" is the closest word to "snow", but not among the top-1 words
+    let res = model.cosine_restricted("snow", 10, 1).unwrap();
+    assert!(res.is_empty());
+    let res = model.cosine_restricted("rain", 10, 2).unwrap();
+    let only_words: Vec<&str> = res.iter().map(|x| x.0.as_ref()).collect();
+    assert_eq!(only_words, vec!["ice", "snow"]);
+
+    let res = model
+        .analogy_restricted(vec!["rain"], vec![], 10, 3)
+        .unwrap()
+        .words;
+    let only_words: Vec<&str> = res.iter().map(|x| x.0.as_ref()).collect();
+    assert_eq!(only_words.len(), 3);
+    assert!(!only_words.contains(&"rain"));
+}
+
+#[test]
+fn test_loader_limits_and_filters_words() {
+    let model = WordVector::loader()
+        .max_words(2)
+        .load_from_text_reader(Cursor::new(TEXT_MODEL))
+        .unwrap();
+    let words: Vec<&String> = model.get_words().collect();
+    assert_eq!(words, vec!["snow", "ice"]);
+
+    let model = WordVector::loader()
+        .filter(|word| word.len() > 3)
+        .load_from_text_reader(Cursor::new(TEXT_MODEL))
+        .unwrap();
+    let words: Vec<&String> = model.get_words().collect();
+    assert_eq!(words, vec!["snow", "rain"]);
+    assert_eq!(model.matrix().len(), 8);
+}
+
+#[test]
+fn test_loader_skips_binary_vectors_outside_allow_list() {
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let mut bytes = Vec::new();
+    model.write_binary(&mut bytes).unwrap();
+
+    let allowed: HashSet<String> = vec!["sun".to_string(), "ice".to_string()]
+        .into_iter()
+        .collect();
+    let loaded = WordVector::loader()
+        .allow_list(allowed)
+        .load_from_reader(Cursor::new(bytes))
+        .unwrap();
+    let words: Vec<&String> = loaded.get_words().collect();
+    assert_eq!(words, vec!["ice", "sun"]);
+    assert_vectors_close(
+        &loaded.get_vector("sun").unwrap(),
+        &model.get_vector("sun").unwrap(),
+    );
+}
+
+#[test]
+fn test_loader_keeps_raw_vectors_and_norms() {
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    assert!(model.get_vector_raw("ice").is_none());
+    assert!(model.get_norm("ice").is_none());
+
+    let model = WordVector::loader()
+        .keep_norms(true)
+        .load_from_text_reader(Cursor::new(TEXT_MODEL))
+        .unwrap();
+    assert!(model.get_vector_raw("ice").is_none());
+    assert!((model.get_norm("rain").unwrap() - 0.75f32.sqrt()).abs() < 1e-6);
+
+    let model = WordVector::loader()
+        .keep_raw_vectors(true)
+        .load_from_text_reader(Cursor::new(TEXT_MODEL))
+        .unwrap();
+    assert_eq!(model.get_vector_raw("rain").unwrap(), &[0.5, 0.5, 0.0, 0.5]);
+    assert!((model.get_norm("rain").unwrap() - 0.75f32.sqrt()).abs() < 1e-6);
+    let normalized: Vec<f32> = [0.5f32, 0.5, 0.0, 0.5]
+        .iter()
+        .map(|x| x / 0.75f32.sqrt())
+        .collect();
+    assert_vectors_close(&model.get_vector("rain").unwrap(), &normalized);
+    assert!(model.get_vector_raw("hail").is_none());
+
+    let mut bytes = Vec::new();
+    model.write_text(&mut bytes).unwrap();
+    let text = String::from_utf8(bytes).unwrap();
+    assert!(text.contains("rain 0.5 0.5 0 0.5\n"));
+}
+
+#[test]
+fn test_binary_reader_reports_truncated_vector() {
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let mut bytes = Vec::new();
+    model.write_binary(&mut bytes).unwrap();
+    // header, three complete records and the word and half of the vector of the fourth one
+    let record_offset = 4 + (5 + 16 + 1) + (4 + 16 + 1) + (4 + 16 + 1);
+    bytes.truncate(record_offset + 5 + 8);
+
+    let mut reader = WordVectorReader::new_from_reader(Cursor::new(bytes.clone())).unwrap();
+    assert_eq!(reader.by_ref().take_while(|r| r.is_ok()).count(), 3);
+    assert!(reader.next().is_none());
+
+    match WordVector::load_from_reader(Cursor::new(bytes)) {
+        Err(Word2VecError::TruncatedVector { record, offset }) => {
+            assert_eq!(record, 3);
+            assert_eq!(offset, record_offset as u64);
+        }
+        other => panic!("expected a truncated vector, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn test_binary_reader_reports_invalid_words_and_missing_records() {
+    let mut bytes = b"2 4\n".to_vec();
+    bytes.extend_from_slice(b"\xffsnow ");
+    bytes.extend_from_slice(&[0u8; 16]);
+    match WordVector::load_from_reader(Cursor::new(bytes)) {
+        Err(Word2VecError::InvalidUtf8Word { record, offset }) => {
+            assert_eq!(record, 0);
+            assert_eq!(offset, 4);
+        }
+        other => panic!("expected an invalid word, got {:?}", other.err()),
+    }
+
+    let mut bytes = b"2 4\nsnow ".to_vec();
+    bytes.extend_from_slice(&[0u8; 16]);
+    bytes.push(b'\n');
+    match WordVector::load_from_reader(Cursor::new(bytes)) {
+        Err(Word2VecError::VocabularySizeMismatch { expected, found }) => {
+            assert_eq!((expected, found), (2, 1));
+        }
+        other => panic!("expected a vocabulary size mismatch, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn test_huge_vocabulary_size_over_short_file() {
+    let mut bytes = b"99999999999 4\nsnow ".to_vec();
+    bytes.extend_from_slice(&[0u8; 16]);
+    bytes.push(b'\n');
+    match WordVector::loader()
+        .keep_raw_vectors(true)
+        .load_from_reader(Cursor::new(bytes))
+    {
+        Err(Word2VecError::VocabularySizeMismatch { expected, found }) => {
+            assert_eq!((expected, found), (99_999_999_999, 1));
+        }
+        other => panic!("expected a vocabulary size mismatch, got {:?}", other.err()),
+    }
+
+    let text = "99999999999 4\nsnow 1.0 0.0 0.0 0.0\nice 0.9";
+    match WordVector::load_from_text_reader(Cursor::new(text)) {
+        Err(Word2VecError::TruncatedVector { record, .. }) => assert_eq!(record, 1),
+        other => panic!("expected a truncated vector, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn test_huge_vector_size_over_short_file() {
+    for header in &["1 3000000000000000000\n", "1 4000000000\n"] {
+        let mut bytes = header.as_bytes().to_vec();
+        bytes.extend_from_slice(b"ab \x01\x02\x03\x04\n");
+        match WordVector::load_from_reader(Cursor::new(bytes.clone())) {
+            Err(Word2VecError::WrongHeader) => {}
+            other => panic!("expected a wrong header, got {:?}", other.err()),
+        }
+        match WordVector::load_from_text_reader(Cursor::new(bytes)) {
+            Err(Word2VecError::WrongHeader) => {}
+            other => panic!("expected a wrong header, got {:?}", other.err()),
+        }
+    }
+}
+
+#[test]
+fn test_text_reader_reports_invalid_records() {
+    let text = "2 4\nsnow 1.0 0.0 0.0 0.0\nice 0.9 0.1\n";
+    match WordVector::load_from_text_reader(Cursor::new(text)) {
+        Err(Word2VecError::TruncatedVector { record, offset }) => {
+            assert_eq!((record, offset), (1, 25));
+        }
+        other => panic!("expected a truncated vector, got {:?}", other.err()),
+    }
+
+    let text = "1 4\nsnow 1.0 0.0 x 0.0\n";
+    match WordVector::load_from_text_reader(Cursor::new(text)) {
+        Err(Word2VecError::InvalidComponent {
+            record, ref value, ..
+        }) => {
+            assert_eq!(record, 0);
+            assert_eq!(value, "x");
+        }
+        other => panic!("expected an invalid component, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn test_strict_loader_rejects_trailing_data() {
+    let mut bytes = Vec::new();
+    WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL))
+        .unwrap()
+        .write_binary(&mut bytes)
+        .unwrap();
+    let len = bytes.len() as u64;
+    bytes.extend_from_slice(b"\nhail ");
+
+    assert!(WordVector::load_from_reader(Cursor::new(bytes.clone())).is_ok());
+    match WordVector::loader()
+        .strict(true)
+        .load_from_reader(Cursor::new(bytes))
+    {
+        Err(Word2VecError::TrailingData { offset }) => assert_eq!(offset, len + 1),
+        other => panic!("expected trailing data, got {:?}", other.err()),
+    }
+
+    let text = format!("{}\n\n", TEXT_MODEL);
+    let model = WordVector::loader()
+        .strict(true)
+        .max_words(1)
+        .load_from_text_reader(Cursor::new(text))
+        .unwrap();
+    assert_eq!(model.word_count(), 1);
+}
+
+#[test]
+fn test_strict_loader_rejects_invalid_vectors() {
+    let text = "2 4\nsnow 1.0 0.0 0.0 0.0\nice 0.9 NaN 0.0 0.0\n";
+    assert!(WordVector::load_from_text_reader(Cursor::new(text)).is_ok());
+    match WordVector::loader()
+        .strict(true)
+        .max_words(1)
+        .load_from_text_reader(Cursor::new(text))
+    {
+        Err(Word2VecError::NonFiniteComponent { record, offset }) => {
+            assert_eq!((record, offset), (1, 25));
+        }
+        other => panic!("expected a non-finite component, got {:?}", other.err()),
+    }
+
+    let text = "1 4\nsnow 1.0 0.0 0.0 0.0 0.0\n";
+    assert!(WordVector::load_from_text_reader(Cursor::new(text)).is_ok());
+    match WordVector::loader()
+        .strict(true)
+        .load_from_text_reader(Cursor::new(text))
+    {
+        Err(Word2VecError::DimensionMismatch {
+            expected, found, ..
+        }) => assert_eq!((expected, found), (4, 5)),
+        other => panic!("expected a dimension mismatch, got {:?}", other.err()),
+    }
+}
+
+/// Binary model whose second word is cut off in the middle of a multi-byte character
+fn binary_model_with_invalid_word() -> Vec<u8> {
+    let mut bytes = b"3 4\n".to_vec();
+    for (word, x) in &[
+        (&b"snow"[..], 1.0f32),
+        (&b"caf\xc3"[..], 2.0),
+        (&b"ice"[..], 3.0),
+    ] {
+        bytes.extend_from_slice(word);
+        bytes.push(b' ');
+        for value in &[*x, 1.0, 0.0, 0.0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.push(b'\n');
+    }
+    bytes
+}
+
+#[test]
+fn test_utf8_policies() {
+    let bytes = binary_model_with_invalid_word();
+    match WordVector::load_from_reader(Cursor::new(bytes.clone())) {
+        Err(Word2VecError::InvalidUtf8Word { record, offset }) => {
+            assert_eq!((record, offset), (1, 26));
+        }
+        other => panic!("expected an invalid word, got {:?}", other.err()),
+    }
+
+    let model = WordVector::loader()
+        .utf8_policy(Utf8Policy::Lossy)
+        .load_from_reader(Cursor::new(bytes.clone()))
+        .unwrap();
+    assert_eq!(model.index_of("caf\u{fffd}"), Some(1));
+
+    let model = WordVector::loader()
+        .utf8_policy(Utf8Policy::Skip)
+        .load_from_reader(Cursor::new(bytes.clone()))
+        .unwrap();
+    let words: Vec<&String> = model.get_words().collect();
+    assert_eq!(words, vec!["snow", "ice"]);
+
+    let model = WordVector::loader()
+        .utf8_policy(Utf8Policy::Raw)
+        .load_from_reader(Cursor::new(bytes))
+        .unwrap();
+    assert_eq!(model.word_count(), 3);
+    assert_eq!(model.index_of("caf\u{fffd}"), None);
+    assert_eq!(model.index_of_bytes(b"caf\xc3"), Some(1));
+    assert_eq!(model.index_of_bytes(b"ice"), Some(2));
+    assert_eq!(model.word_at(1), Some("caf\u{fffd}"));
+    assert!(model.get_vector_bytes(b"caf\xc3").is_some());
+
+    // the name and the bytes of a raw word are trimmed alike
+    let mut bytes = b"1 4\n\xc2\xa0caf\xc3 ".to_vec();
+    bytes.extend_from_slice(&[0u8; 16]);
+    let model = WordVector::loader()
+        .utf8_policy(Utf8Policy::Raw)
+        .load_from_reader(Cursor::new(bytes))
+        .unwrap();
+    assert_eq!(model.word_at(0), Some("\u{a0}caf\u{fffd}"));
+    assert_eq!(model.index_of_bytes(b"\xc2\xa0caf\xc3"), Some(0));
+}
+
+#[test]
+fn test_text_readers_keep_raw_words() {
+    let text = b"2 4\ncaf\xc3 1.0 0.0 0.0 0.0\nice 0.9 0.1 0.0 0.0\n";
+    let mut reader = TextWordVectorReader::new_from_reader(Cursor::new(&text[..]))
+        .unwrap()
+        .utf8_policy(Utf8Policy::Raw);
+    let records: Vec<(String, Vec<f32>)> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+    assert_eq!(records[0].0, "caf\u{fffd}");
+    assert_eq!(records[0].1, vec![1.0, 0.0, 0.0, 0.0]);
+
+    let glove = b"ice 0.9 0.1 0.0 0.0\ncaf\xc3 au lait 1.0 0.0 0.0 0.0\n";
+    let model = WordVector::loader()
+        .utf8_policy(Utf8Policy::Raw)
+        .load_from_glove_reader(Cursor::new(&glove[..]))
+        .unwrap();
+    assert_eq!(model.index_of_bytes(b"caf\xc3 au lait"), Some(1));
+    let model = WordVector::loader()
+        .utf8_policy(Utf8Policy::Skip)
+        .load_from_glove_reader(Cursor::new(&glove[..]))
+        .unwrap();
+    assert_eq!(model.word_count(), 1);
+
+    // invalid bytes after the word are reported as invalid components
+    let text = b"1 4\nab 1.0 0.0 0.0 \xff\xff\xff\xff\n";
+    match WordVector::loader()
+        .utf8_policy(Utf8Policy::Raw)
+        .load_from_text_reader(Cursor::new(&text[..]))
+    {
+        Err(Word2VecError::InvalidComponent { record, .. }) => assert_eq!(record, 0),
+        other => panic!("expected an invalid component, got {:?}", other.err()),
+    }
+    let glove = b"ice 0.9 0.1 0.0 0.0\nab 1.0 0.0 0.0 \xff\xff\xff\xff\n";
+    match WordVector::loader()
+        .utf8_policy(Utf8Policy::Raw)
+        .load_from_glove_reader(Cursor::new(&glove[..]))
+    {
+        Err(Word2VecError::InvalidComponent { record, .. }) => assert_eq!(record, 1),
+        other => panic!("expected an invalid component, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn test_binary_element_types_and_byte_orders() {
+    let layouts = [
+        (ElementType::F32, Endianness::Little),
+        (ElementType::F32, Endianness::Big),
+        (ElementType::F64, Endianness::Little),
+        (ElementType::F64, Endianness::Big),
+    ];
+    for &(element_type, endianness) in &layouts {
+        let mut writer = WordVectorWriter::new_from_writer(Vec::new(), 2, 4)
+            .unwrap()
+            .element_type(element_type)
+            .endianness(endianness);
+        writer.write_vector("snow", &[1.0, 2.0, 0.0, 0.5]).unwrap();
+        writer.write_vector("ice", &[0.0, 1.0, 0.0, 0.0]).unwrap();
+        let bytes = writer.into_inner().unwrap();
+        assert_eq!(bytes.len(), 4 + 2 * (4 * element_type.size() + 1) + 5 + 4);
+
+        let records: Vec<(String, Vec<f32>)> =
+            WordVectorReader::new_from_reader(Cursor::new(bytes.clone()))
+                .unwrap()
+                .element_type(element_type)
+                .endianness(endianness)
+                .collect::<Result<_, _>>()
+                .unwrap();
+        assert_eq!(records[0].1, vec![1.0, 2.0, 0.0, 0.5]);
+        assert_eq!(records[1].0, "ice");
+
+        let model = WordVector::loader()
+            .element_type(element_type)
+            .endianness(endianness)
+            .strict(true)
+            .load_from_reader(Cursor::new(bytes))
+            .unwrap();
+        assert_eq!(model.cosine("snow", 1).unwrap()[0].0, "ice");
+    }
+}
+
+#[cfg(feature = "half")]
+#[test]
+fn test_half_precision_storage() {
+    use word2vec::wordvectors::Precision;
+
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    assert_eq!(model.precision(), Precision::F32);
+    for &precision in &[Precision::F16, Precision::Bf16] {
+        let half = WordVector::loader()
+            .precision(precision)
+            .load_from_text_reader(Cursor::new(TEXT_MODEL))
+            .unwrap();
+        assert_eq!(half.precision(), precision);
+        for word in model.get_words() {
+            let expected = model.get_vector(word).unwrap();
+            let actual = half.get_vector(word).unwrap();
+            for (x, y) in actual.iter().zip(expected.iter()) {
+                assert!((x - y).abs() < 1e-2);
+            }
+        }
+        let words = |result: Vec<(String, f32)>| -> Vec<String> {
+            result.into_iter().map(|(word, _)| word).collect()
+        };
+        assert_eq!(
+            words(half.cosine("snow", 3).unwrap()),
+            words(model.cosine("snow", 3).unwrap())
+        );
+        assert_eq!(
+            words(half.analogy(vec!["ice"], vec!["sun"], 2).unwrap().words),
+            words(model.analogy(vec!["ice"], vec!["sun"], 2).unwrap().words)
+        );
+    }
+}
+
+#[cfg(feature = "half")]
+#[test]
+fn test_half_precision_binary_files() {
+    for &element_type in &[ElementType::F16, ElementType::Bf16] {
+        let mut writer = WordVectorWriter::new_from_writer(Vec::new(), 1, 4)
+            .unwrap()
+            .element_type(element_type)
+            .endianness(Endianness::Big);
+        writer.write_vector("snow", &[1.0, 0.5, -2.0, 0.0]).unwrap();
+        let bytes = writer.into_inner().unwrap();
+        assert_eq!(bytes.len(), 4 + 5 + 8 + 1);
+
+        let records: Vec<(String, Vec<f32>)> =
+            WordVectorReader::new_from_reader(Cursor::new(bytes))
+                .unwrap()
+                .element_type(element_type)
+                .endianness(Endianness::Big)
+                .collect::<Result<_, _>>()
+                .unwrap();
+        assert_eq!(records[0].1, vec![1.0, 0.5, -2.0, 0.0]);
+    }
+}
+
+#[test]
+fn test_int8_storage() {
+    use word2vec::wordvectors::{Precision, Scale};
+
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    for &scale in &[Scale::PerVector, Scale::PerDimension] {
+        let precision = Precision::Int8(scale);
+        let quantized = WordVector::loader()
+            .precision(precision)
+            .load_from_text_reader(Cursor::new(TEXT_MODEL))
+            .unwrap();
+        assert_eq!(quantized.precision(), precision);
+        for word in model.get_words() {
+            let expected = model.get_vector(word).unwrap();
+            let actual = quantized.get_vector(word).unwrap();
+            for (x, y) in actual.iter().zip(expected.iter()) {
+                assert!((x - y).abs() < 1e-2);
+            }
+        }
+        let expected = model.cosine("snow", 3).unwrap();
+        let actual = quantized.cosine("snow", 3).unwrap();
+        for ((word, score), (expected_word, expected_score)) in actual.iter().zip(&expected) {
+            assert_eq!(word, expected_word);
+            assert!((score - expected_score).abs() < 2e-2);
+        }
+
+        let converted = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL))
+            .unwrap()
+            .with_precision(precision);
+        assert_eq!(converted.precision(), precision);
+        assert_vectors_close(&converted.matrix(), &quantized.matrix());
+    }
+}
+
+#[test]
+fn test_reconstruction_error() {
+    use word2vec::wordvectors::{Precision, Scale};
+
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let error = model.reconstruction_error(Precision::F32);
+    assert_eq!(error.mean_squared, 0.0);
+    assert_eq!(error.max_absolute, 0.0);
+    for &scale in &[Scale::PerVector, Scale::PerDimension] {
+        let error = model.reconstruction_error(Precision::Int8(scale));
+        assert!(error.max_absolute > 0.0);
+        // rounding is off by at most half a step of 1 / 127
+        assert!(error.max_absolute <= 0.5 / 127.0 + 1e-6);
+        assert!(error.mean_squared <= error.max_absolute * error.max_absolute);
+    }
+    // measuring does not change the model
+    assert_eq!(model.precision(), Precision::F32);
+}
+
+#[test]
+fn test_product_quantization() {
+    use word2vec::pqvectors::{PqWordVector, ProductQuantizer};
+
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    // with a centroid for every word, the codes reproduce the vectors exactly
+    let compressed = ProductQuantizer::new(2).train(&model).unwrap();
+    assert_eq!(compressed.word_count(), 4);
+    assert_eq!(compressed.subquantizers(), 2);
+    for word in model.get_words() {
+        assert_vectors_close(
+            &compressed.get_vector(word).unwrap(),
+            &model.get_vector(word).unwrap(),
+        );
+    }
+    let expected = model.cosine("snow", 3).unwrap();
+    let actual = compressed.cosine("snow", 3).unwrap();
+    for ((word, score), (expected_word, expected_score)) in actual.iter().zip(&expected) {
+        assert_eq!(word, expected_word);
+        assert!((score - expected_score).abs() < 1e-5);
+    }
+
+    let path = temp_path("vectors.pq");
+    compressed.save(&path).unwrap();
+    let loaded = PqWordVector::load(&path).unwrap();
+    assert_eq!(
+        loaded.get_words().collect::<Vec<_>>(),
+        compressed.get_words().collect::<Vec<_>>()
+    );
+    assert_eq!(loaded.cosine("snow", 3), compressed.cosine("snow", 3));
+    std::fs::remove_file(&path).unwrap();
+
+    // fewer centroids than words approximate the vectors
+    let coarse = ProductQuantizer::new(1).centroids(2).train(&model).unwrap();
+    assert_eq!(coarse.cosine("snow", 3).unwrap().len(), 3);
+}
+
+#[test]
+fn test_product_quantization_errors() {
+    use word2vec::pqvectors::{PqWordVector, ProductQuantizer};
+
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    for quantizer in &[
+        ProductQuantizer::new(3),
+        ProductQuantizer::new(0),
+        ProductQuantizer::new(2).centroids(257),
+        ProductQuantizer::new(2).sample_size(0),
+    ] {
+        match quantizer.train(&model) {
+            Err(Word2VecError::InvalidParameter(_)) => {}
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("invalid parameters were accepted"),
+        }
+    }
+    match PqWordVector::load_from_reader(Cursor::new(TEXT_MODEL)) {
+        Err(Word2VecError::InvalidQuantizedFile(_)) => {}
+        Err(err) => panic!("unexpected error {}", err),
+        Ok(_) => panic!("text model was loaded as product-quantized model"),
+    }
+
+    let empty = WordVector::load_from_text_reader(Cursor::new("1 0\nsnow\n")).unwrap();
+    match ProductQuantizer::new(1).train(&empty) {
+        Err(Word2VecError::InvalidParameter(_)) => {}
+        Err(err) => panic!("unexpected error {}", err),
+        Ok(_) => panic!("vectors without components were quantized"),
+    }
+
+    let mut bytes = Vec::new();
+    ProductQuantizer::new(2)
+        .train(&model)
+        .unwrap()
+        .write(&mut bytes)
+        .unwrap();
+    let corruptions: [(usize, u64); 3] = [
+        // no components
+        (24, 0),
+        // a codebook size which overflows
+        (24, 1 << 62),
+        // and the same without any words
+        (16, 0),
+    ];
+    let mut corrupt = bytes.clone();
+    for &(offset, value) in &corruptions {
+        corrupt[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        match PqWordVector::load_from_reader(Cursor::new(&corrupt)) {
+            Err(Word2VecError::InvalidQuantizedFile(_)) => {}
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("corrupt header was accepted"),
+        }
+    }
+}
+
+#[test]
+fn test_hnsw_index() {
+    use word2vec::hnsw::{HnswBuilder, HnswIndex};
+
+    let model = WordVector::load_from_text_reader(Cursor::new(synthetic_model(300, 8))).unwrap();
+    let index = HnswBuilder::new().m(8).build(&model).unwrap();
+    assert!(index.most_similar("unknown", 5).is_none());
+    // tracking as many candidates as there are words makes the search exact
+    let index = index.ef_search(300);
+    for word in &["w0", "w42", "w299"] {
+        assert_eq!(index.most_similar(word, 5), model.cosine(word, 5));
+    }
+
+    let path = temp_path("vectors.hnsw");
+    index.save(&path).unwrap();
+    let loaded = HnswIndex::load(&path, &model).unwrap();
+    assert_eq!(loaded.most_similar("w42", 5), index.most_similar("w42", 5));
+
+    let other = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    match HnswIndex::load(&path, &other) {
+        Err(Word2VecError::InvalidIndexFile(_)) => {}
+        Err(err) => panic!("unexpected error {}", err),
+        Ok(_) => panic!("index was loaded for a different model"),
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_hnsw_recall() {
+    use word2vec::hnsw::HnswBuilder;
+
+    let model = WordVector::load_from_text_reader(Cursor::new(synthetic_model(1000, 8))).unwrap();
+    let index = HnswBuilder::new().build(&model).unwrap();
+    let mut found = 0;
+    for i in (0..1000).step_by(10) {
+        let word = format!("w{}", i);
+        let expected: Vec<String> = model
+            .cosine(&word, 10)
+            .unwrap()
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect();
+        for (word, _) in index.most_similar(&word, 10).unwrap() {
+            if expected.contains(&word) {
+                found += 1;
+            }
+        }
+    }
+    assert!(found >= 950, "recall of {} out of 1000", found);
+
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    match HnswBuilder::new().m(1).build(&model) {
+        Err(Word2VecError::InvalidParameter(_)) => {}
+        _ => panic!("M of 1 was accepted"),
+    }
+}
+
+#[test]
+fn test_cosine_batch() {
+    let model = WordVector::load_from_text_reader(Cursor::new(synthetic_model(300, 8))).unwrap();
+    let words: Vec<String> = (0..150).map(|i| format!("w{}", 2 * i)).collect();
+    let mut words: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+    words.insert(70, "unknown");
+    let results = model.cosine_batch(&words, 5);
+    assert_eq!(results.len(), words.len());
+    for (word, result) in words.iter().zip(results) {
+        assert_eq!(result, model.cosine(word, 5));
+    }
+}
+
+#[test]
+fn test_similar_by_vector() {
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    // the scale of the query doesn't matter
+    let result = model
+        .similar_by_vector(&[2.0, 0.0, 0.0, 0.0], 2, &[])
+        .unwrap();
+    assert_eq!(result[0].0, "snow");
+    assert!((result[0].1 - 1.0).abs() < 1e-6);
+    assert_eq!(result[1].0, "ice");
+
+    let snow = model.get_vector("snow").unwrap();
+    let result = model
+        .similar_by_vector(&snow, 3, &["snow", "unknown"])
+        .unwrap();
+    assert_eq!(result, model.cosine("snow", 3).unwrap());
+
+    let result = model.similar_by_vector(&[0.0; 4], 4, &[]).unwrap();
+    assert!(result.iter().all(|&(_, score)| score == 0.0));
+
+    match model.similar_by_vector(&[1.0, 0.0], 2, &[]) {
+        Err(Word2VecError::WrongVectorSize {
+            expected: 4,
+            found: 2,
+        }) => {}
+        _ => panic!("query of the wrong size was accepted"),
+    }
+    for &x in &[f32::NAN, f32::INFINITY] {
+        match model.similar_by_vector(&[1.0, x, 0.0, 0.0], 2, &[]) {
+            Err(Word2VecError::InvalidParameter(_)) => {}
+            _ => panic!("query with a non-finite component was accepted"),
+        }
+    }
+}
+
+#[test]
+fn test_analogy_methods() {
+    use word2vec::wordvectors::AnalogyMethod;
+
+    let model = WordVector::load_from_text_reader(Cursor::new(
+        "5 4
+man 1.0 0.0 0.0 0.2
+woman -1.0 0.0 0.0 0.2
+king 1.0 1.0 0.0 0.2
+queen -1.0 1.0 0.0 0.2
+apple 0.0 0.0 1.0 0.2
+",
+    ))
+    .unwrap();
+    assert_eq!(
+        model
+            .analogy_with(AnalogyMethod::CosAdd, vec!["woman", "king"], vec!["man"], 2)
+            .unwrap(),
+        model
+            .analogy(vec!["woman", "king"], vec!["man"], 2)
+            .unwrap()
+    );
+    for &method in &[
+        AnalogyMethod::CosAdd,
+        AnalogyMethod::CosMul,
+        AnalogyMethod::PairDirection,
+    ] {
+        let result = model
+            .analogy_with(method, vec!["woman", "king"], vec!["man"], 2)
+            .unwrap()
+            .words;
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].0, "queen", "{:?}", method);
+        assert!(result[0].1 > result[1].1);
+    }
+    // the offset from king to queen is parallel to the one from man to woman
+    let result = model
+        .analogy_with(
+            AnalogyMethod::PairDirection,
+            vec!["woman", "king"],
+            vec!["man"],
+            1,
+        )
+        .unwrap()
+        .words;
+    assert!((result[0].1 - 1.0).abs() < 1e-5);
+    assert!(model
+        .analogy_with(AnalogyMethod::PairDirection, vec!["king"], vec!["man"], 1)
+        .is_err());
+}
+
+#[test]
+fn test_analogy_unknown_words() {
+    use word2vec::wordvectors::AnalogyMethod;
+
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    let result = model
+        .analogy(vec!["ice", "hail"], vec!["sun", "fog"], 2)
+        .unwrap();
+    assert_eq!(result.unknown, vec!["hail", "fog"]);
+    assert_eq!(
+        result.words,
+        model.analogy(vec!["ice"], vec!["sun"], 2).unwrap().words
+    );
+    assert!(result.words.iter().all(|(_, score)| score.is_finite()));
+
+    match model.analogy(vec!["hail"], vec!["fog"], 2) {
+        Err(Word2VecError::UnknownWords(words)) => assert_eq!(words, vec!["hail", "fog"]),
+        _ => panic!("analogy of unknown words was answered"),
+    }
+    match model.analogy_with(
+        AnalogyMethod::PairDirection,
+        vec!["ice", "hail"],
+        vec!["snow"],
+        2,
+    ) {
+        Err(Word2VecError::UnknownWords(words)) => assert_eq!(words, vec!["hail"]),
+        _ => panic!("pair direction with an unknown word was answered"),
+    }
+}
+
+#[test]
+fn test_analogy_evaluation() {
+    use word2vec::evaluation::AnalogyEvaluation;
+
+    let model = WordVector::load_from_text_reader(Cursor::new(
+        "5 4
+man 1.0 0.0 0.0 0.2
+woman -1.0 0.0 0.0 0.2
+king 1.0 1.0 0.0 0.2
+queen -1.0 1.0 0.0 0.2
+apple 0.0 0.0 1.0 0.2
+",
+    ))
+    .unwrap();
+    let questions = ": royal
+man woman king queen
+Man Woman King Queen
+man woman king apple
+man woman king prince
+
+: gender
+man king woman queen
+";
+    let scores = AnalogyEvaluation::new()
+        .evaluate(&model, Cursor::new(questions))
+        .unwrap();
+    assert_eq!(scores.sections.len(), 2);
+    let royal = &scores.sections[0];
+    assert_eq!(royal.name, "royal");
+    assert_eq!((royal.correct, royal.incorrect, royal.oov), (2, 1, 1));
+    assert!((royal.accuracy() - 2.0 / 3.0).abs() < 1e-6);
+    assert_eq!(scores.sections[1].name, "gender");
+    assert_eq!(scores.sections[1].correct, 1);
+    assert_eq!(scores.total.name, "Total accuracy");
+    assert_eq!(
+        (
+            scores.total.correct,
+            scores.total.incorrect,
+            scores.total.oov
+        ),
+        (3, 1, 1)
+    );
+    assert!((scores.oov_ratio() - 0.2).abs() < 1e-6);
+
+    let scores = AnalogyEvaluation::new()
+        .case_insensitive(false)
+        .restrict_vocab(4)
+        .evaluate(&model, Cursor::new(questions))
+        .unwrap();
+    let royal = &scores.sections[0];
+    assert_eq!((royal.correct, royal.incorrect, royal.oov), (1, 0, 3));
+
+    for &(invalid, expected) in &[
+        ("man woman king queen\n", 1),
+        (": royal\n\nman woman king\n", 3),
+    ] {
+        match AnalogyEvaluation::new().evaluate(&model, Cursor::new(invalid)) {
+            Err(Word2VecError::InvalidEvaluationFile { line, .. }) => assert_eq!(line, expected),
+            _ => panic!("invalid analogy file was accepted"),
+        }
+    }
+}
+
+#[test]
+fn test_evaluation_of_raw_words() {
+    use word2vec::evaluation::{AnalogyEvaluation, SimilarityEvaluation};
+
+    let model = WordVector::loader()
+        .utf8_policy(Utf8Policy::Raw)
+        .load_from_reader(Cursor::new(binary_model_with_invalid_word()))
+        .unwrap();
+    let questions = ": raw\nsnow ice caf\u{fffd} snow\n";
+    let scores = AnalogyEvaluation::new()
+        .evaluate(&model, Cursor::new(questions))
+        .unwrap();
+    assert_eq!(
+        (
+            scores.total.correct,
+            scores.total.incorrect,
+            scores.total.oov
+        ),
+        (0, 0, 1)
+    );
+
+    let pairs = "snow ice 8.0\nsnow caf\u{fffd} 2.0\n";
+    let scores = SimilarityEvaluation::new()
+        .evaluate(&model, Cursor::new(pairs))
+        .unwrap();
+    assert_eq!((scores.pairs, scores.oov), (1, 1));
+}
+
+#[test]
+fn test_similarity_evaluation() {
+    use word2vec::evaluation::SimilarityEvaluation;
+
+    let model = WordVector::load_from_text_reader(Cursor::new(TEXT_MODEL)).unwrap();
+    assert!((model.similarity("snow", "snow").unwrap() - 1.0).abs() < 1e-6);
+    assert_eq!(model.similarity("snow", "sun"), Some(0.0));
+    assert_eq!(model.similarity("snow", "hail"), None);
+
+    let pairs = "Word 1,Word 2,Human (mean)
+snow,ice,9.5
+ice,rain,6.0
+snow,rain,5.0
+# comment
+SUN,snow,1.0
+sun,hail,3.0
+";
+    let scores = SimilarityEvaluation::new()
+        .delimiter(',')
+        .evaluate(&model, Cursor::new(pairs))
+        .unwrap();
+    assert_eq!((scores.pairs, scores.oov), (4, 1));
+    assert!((scores.spearman - 1.0).abs() < 1e-6);
+    assert!(scores.pearson > 0.8 && scores.pearson < 1.0);
+    assert!((scores.oov_ratio() - 0.2).abs() < 1e-6);
+
+    let scores = SimilarityEvaluation::new()
+        .case_insensitive(false)
+        .evaluate(&model, Cursor::new(pairs.replace(',', "\t")))
+        .unwrap();
+    assert_eq!((scores.pairs, scores.oov), (3, 2));
+
+    for &(invalid, expected) in &[("snow ice\n", 1), ("snow ice 1\nsnow ice high\n", 2)] {
+        match SimilarityEvaluation::new().evaluate(&model, Cursor::new(invalid)) {
+            Err(Word2VecError::InvalidEvaluationFile { line, .. }) => assert_eq!(line, expected),
+            _ => panic!("invalid word pair file was accepted"),
+        }
+    }
+}
+
+#[bench]
+fn bench_cosine(b: &mut Bencher) {
+    let model =
+        WordVector::load_from_text_reader(Cursor::new(synthetic_model(10_000, 100))).unwrap();
+    b.iter(|| model.cosine("w42", 10));
+}
+
+#[bench]
+fn bench_analogy(b: &mut Bencher) {
+    let model =
+        WordVector::load_from_text_reader(Cursor::new(synthetic_model(10_000, 100))).unwrap();
+    b.iter(|| model.analogy(vec!["w1", "w2"], vec!["w3"], 10));
+}
+
+#[bench]
+fn bench_cosine_batch(b: &mut Bencher) {
+    let model =
+        WordVector::load_from_text_reader(Cursor::new(synthetic_model(10_000, 100))).unwrap();
+    let words: Vec<String> = (0..100).map(|i| format!("w{}", i)).collect();
+    let words: Vec<&str> = words.iter().map(|word| word.as_str()).collect();
+    b.iter(|| model.cosine_batch(&words, 10));
+}